@@ -0,0 +1,42 @@
+//! Times the parallel storage walks behind `count_entries` and
+//! `calculate_size`, whose totals are gathered per worker.
+//!
+//! Run with `cargo bench --bench counters`.
+
+use std::fs;
+use std::time::{Duration, Instant};
+
+use drfs::EntryWrapper;
+
+const RUNS: u32 = 20;
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let tmp = tempfile::tempdir().unwrap();
+    for d in 0..100 {
+        let dir = tmp.path().join(format!("d{}", d));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..200 {
+            fs::write(dir.join(format!("f{}", i)), b"x").unwrap();
+        }
+    }
+
+    let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+    assert!(wrapper.load_entry().is_empty());
+
+    println!(
+        "count_entries:  {:?}",
+        time(|| assert_eq!(wrapper.count_entries(), 100 + 100 * 200))
+    );
+    println!(
+        "calculate_size: {:?}",
+        time(|| assert_eq!(wrapper.calculate_size(), 100 * 200))
+    );
+}
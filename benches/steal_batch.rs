@@ -0,0 +1,66 @@
+//! Scans the same deep and wide trees with a few steal batch sizes.
+//!
+//! Run with `cargo bench --bench steal_batch`.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use drfs::{EntryWrapper, ScanOptions};
+
+const RUNS: u32 = 5;
+
+fn build_deep(root: &Path) {
+    for branch in 0..8 {
+        let mut dir = root.join(format!("b{}", branch));
+        for level in 0..40 {
+            dir = dir.join(format!("l{}", level));
+            fs::create_dir_all(&dir).unwrap();
+            for i in 0..5 {
+                fs::write(dir.join(format!("f{}", i)), b"x").unwrap();
+            }
+        }
+    }
+}
+
+fn build_wide(root: &Path) {
+    for d in 0..200 {
+        let dir = root.join(format!("d{}", d));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..20 {
+            fs::write(dir.join(format!("f{}", i)), b"x").unwrap();
+        }
+    }
+}
+
+fn time_scan(root: &Path, steal_batch: Option<usize>) -> Duration {
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        let mut wrapper = EntryWrapper::new_with_memstorage(root).unwrap();
+        wrapper.set_options(ScanOptions {
+            steal_batch,
+            collect_times: false,
+            ..ScanOptions::default()
+        });
+        let start = Instant::now();
+        let errors = wrapper.load_entry();
+        total += start.elapsed();
+        assert!(errors.is_empty());
+    }
+    total / RUNS
+}
+
+fn main() {
+    for (name, build) in [("deep", build_deep as fn(&Path)), ("wide", build_wide)] {
+        let tmp = tempfile::tempdir().unwrap();
+        build(tmp.path());
+        for steal_batch in [None, Some(1), Some(4), Some(16), Some(64)] {
+            println!(
+                "{:>5} tree, steal batch {:>7}: {:?}",
+                name,
+                steal_batch.map_or("default".to_string(), |n| n.to_string()),
+                time_scan(tmp.path(), steal_batch)
+            );
+        }
+    }
+}
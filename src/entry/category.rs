@@ -0,0 +1,45 @@
+use std::fmt;
+
+/// Coarse content classification of a file, derived from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FileCategory {
+    Media,
+    Code,
+    Archive,
+    Document,
+    Other,
+}
+
+impl FileCategory {
+    /// Category for a lowercased extension without the leading dot.
+    pub fn from_extension(ext: &str) -> FileCategory {
+        match ext {
+            "mp4" | "mkv" | "avi" | "mov" | "webm" | "wmv" | "flv" | "mp3" | "flac" | "wav"
+            | "ogg" | "opus" | "m4a" | "aac" | "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp"
+            | "svg" | "tiff" | "heic" | "raw" => FileCategory::Media,
+            "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "go" | "py" | "js" | "ts" | "jsx" | "tsx"
+            | "java" | "kt" | "rb" | "php" | "cs" | "swift" | "sh" | "bash" | "lua" | "hs"
+            | "ml" | "scala" | "toml" | "yaml" | "yml" | "json" | "html" | "css" => {
+                FileCategory::Code
+            }
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" | "lz4" | "iso"
+            | "dmg" | "deb" | "rpm" | "jar" => FileCategory::Archive,
+            "pdf" | "doc" | "docx" | "odt" | "rtf" | "txt" | "md" | "xls" | "xlsx" | "ods"
+            | "csv" | "ppt" | "pptx" | "odp" | "epub" | "tex" => FileCategory::Document,
+            _ => FileCategory::Other,
+        }
+    }
+}
+
+impl fmt::Display for FileCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FileCategory::Media => "media",
+            FileCategory::Code => "code",
+            FileCategory::Archive => "archive",
+            FileCategory::Document => "document",
+            FileCategory::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,87 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Kind of content found from the first bytes of a file, regardless of its
+/// extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Application,
+    Archive,
+    Audio,
+    Book,
+    Document,
+    Font,
+    Image,
+    Text,
+    Video,
+}
+
+impl FromStr for ContentKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ContentKind, String> {
+        match s {
+            "application" => Ok(ContentKind::Application),
+            "archive" => Ok(ContentKind::Archive),
+            "audio" => Ok(ContentKind::Audio),
+            "book" => Ok(ContentKind::Book),
+            "document" => Ok(ContentKind::Document),
+            "font" => Ok(ContentKind::Font),
+            "image" => Ok(ContentKind::Image),
+            "text" => Ok(ContentKind::Text),
+            "video" => Ok(ContentKind::Video),
+            _ => Err(format!(
+                "unknown content kind '{}', expected application, archive, audio, book, \
+                 document, font, image, text or video",
+                s
+            )),
+        }
+    }
+}
+
+/// Content kind of the file at `p` from its magic bytes, `None` if it can't
+/// be read or isn't recognized.
+#[cfg(feature = "content-type")]
+pub(crate) fn detect(p: &Path) -> Option<ContentKind> {
+    use infer::MatcherType;
+
+    match infer::get_from_path(p).ok()??.matcher_type() {
+        MatcherType::App => Some(ContentKind::Application),
+        MatcherType::Archive => Some(ContentKind::Archive),
+        MatcherType::Audio => Some(ContentKind::Audio),
+        MatcherType::Book => Some(ContentKind::Book),
+        MatcherType::Doc => Some(ContentKind::Document),
+        MatcherType::Font => Some(ContentKind::Font),
+        MatcherType::Image => Some(ContentKind::Image),
+        MatcherType::Text => Some(ContentKind::Text),
+        MatcherType::Video => Some(ContentKind::Video),
+        MatcherType::Custom => None,
+    }
+}
+
+#[cfg(not(feature = "content-type"))]
+pub(crate) fn detect(_p: &Path) -> Option<ContentKind> {
+    None
+}
+
+#[cfg(all(test, feature = "content-type"))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn detects_by_magic_bytes_not_extension() {
+        let tmp = tempfile::tempdir().unwrap();
+        let png = tmp.path().join("picture.dat");
+        fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let plain = tmp.path().join("notes.png");
+        fs::write(&plain, b"just some words").unwrap();
+
+        assert_eq!(detect(&png), Some(ContentKind::Image));
+        assert_eq!(detect(&plain), None);
+        assert_eq!(detect(&tmp.path().join("missing")), None);
+    }
+}
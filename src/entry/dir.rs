@@ -0,0 +1,1007 @@
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::content;
+use super::event::WalkEvent;
+use super::sample::{self, ApproxResult};
+use super::{
+    dev, format_path, gid, inode, io_path_error, mode, path_error, path_name, read_times,
+    time_errors, uid, Entry, ErrorCollector, ScanOptions,
+};
+use crate::matcher::Matcher;
+use crate::store::Storage;
+use crate::walk::{run_parallel, run_parallel_with_state};
+use crate::GenericError;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirEntry {
+    path: Box<PathBuf>,
+    name: String,
+    parent: Option<Arc<str>>,
+    depth: usize,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mode: Option<u32>,
+    readonly: bool,
+    dev: Option<u64>,
+    inode: Option<(u64, u64)>,
+    mount_point: bool,
+    read_time: Option<Duration>,
+    /// Stored by key only, the children are entries of their own.
+    children: Vec<String>,
+    #[serde(with = "super::time_serde::result")]
+    accessed: Result<SystemTime, Arc<io::Error>>,
+    #[serde(with = "super::time_serde::result")]
+    modified: Result<SystemTime, Arc<io::Error>>,
+    #[serde(with = "super::time_serde::result")]
+    created: Result<SystemTime, Arc<io::Error>>,
+    #[serde(with = "super::time_serde")]
+    scanned_at: SystemTime,
+}
+
+impl DirEntry {
+    pub fn new<P: AsRef<Path>>(
+        p: P,
+        parent: Option<Arc<str>>,
+        depth: usize,
+        metadata: &Metadata,
+        options: &ScanOptions,
+    ) -> DirEntry {
+        let p = p.as_ref();
+        let (accessed, modified, created) = read_times(metadata, options.collect_times);
+        DirEntry {
+            path: Box::new(p.to_path_buf()),
+            name: path_name(p),
+            parent,
+            depth,
+            uid: uid(metadata),
+            gid: gid(metadata),
+            mode: mode(metadata),
+            readonly: metadata.permissions().readonly(),
+            dev: dev(metadata),
+            inode: inode(metadata),
+            mount_point: false,
+            read_time: None,
+            children: vec![],
+            accessed,
+            modified,
+            created,
+            scanned_at: SystemTime::now(),
+        }
+    }
+
+    pub fn get_format_path(&self) -> String {
+        format_path(&self.path)
+    }
+
+    /// The path as scanned, without the lossy conversion of `get_format_path`.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_parent(&self) -> Option<String> {
+        self.parent.as_deref().map(str::to_string)
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Owning user id, `None` on platforms without Unix ownership.
+    pub fn get_uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// Owning group id, `None` on platforms without Unix ownership.
+    pub fn get_gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// Unix mode bits, permissions and file type, `None` on other platforms.
+    pub fn get_mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Whether the read-only attribute is set, on Unix whether no write bit is set.
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Id of the device holding the directory, `None` on non-Unix platforms.
+    pub fn get_dev(&self) -> Option<u64> {
+        self.dev
+    }
+
+    /// `(device, inode)` identifying the directory on disk, the same for
+    /// every path leading to it through symlinks.
+    pub fn get_inode(&self) -> Option<(u64, u64)> {
+        self.inode
+    }
+
+    /// Whether the directory sits on another device than its parent, i.e.
+    /// something is mounted on it. Only set when the scan ran with
+    /// `ScanOptions::detect_mounts`, and never for the scan root, whose
+    /// parent isn't read.
+    pub fn is_mount_point(&self) -> bool {
+        self.mount_point
+    }
+
+    pub fn get_accessed(&self) -> Option<SystemTime> {
+        self.accessed.as_ref().ok().copied()
+    }
+
+    pub fn get_modified(&self) -> Option<SystemTime> {
+        self.modified.as_ref().ok().copied()
+    }
+
+    pub fn get_created(&self) -> Option<SystemTime> {
+        self.created.as_ref().ok().copied()
+    }
+
+    /// Why the time getters return `None`, one `"<field>: <error>"` line per
+    /// time that couldn't be read.
+    pub fn get_time_errors(&self) -> Vec<String> {
+        time_errors(&self.accessed, &self.modified, &self.created)
+    }
+
+    /// Wall-clock time at which this entry was read from disk.
+    pub fn get_scanned_at(&self) -> SystemTime {
+        self.scanned_at
+    }
+
+    /// How long reading the directory and its children's metadata took, only
+    /// measured when scanning with `ScanOptions::time_reads`.
+    pub fn get_read_time(&self) -> Option<Duration> {
+        self.read_time
+    }
+
+    /// Storage keys of the direct children, filled in once the directory is loaded.
+    pub fn get_children(&self) -> &[String] {
+        &self.children
+    }
+
+    /// Reads the directory, records the keys of its children and hands each
+    /// child to `on_child` as soon as it is read, so huge directories are never
+    /// materialized in full. Returns the errors hit while reading.
+    fn get_load_children<F>(&mut self, options: &ScanOptions, mut on_child: F) -> Vec<GenericError>
+    where
+        F: FnMut(Entry),
+    {
+        let mut errors = vec![];
+        let started = options.time_reads.then(Instant::now);
+
+        #[cfg(test)]
+        if let Err(e) = test_seam::maybe_deny(&self.name) {
+            return vec![io_path_error(&self.path, e)];
+        }
+        let read_dir = match fs::read_dir(self.path.as_path()) {
+            Ok(read_dir) => read_dir,
+            Err(e) => return vec![io_path_error(&self.path, e)],
+        };
+
+        // Shared by every child instead of each holding its own copy.
+        let parent: Arc<str> = Arc::from(self.get_format_path());
+        self.children.clear();
+        for dir_entry in read_dir {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(e) => {
+                    errors.push(path_error(&self.path, e));
+                    continue;
+                }
+            };
+            match Entry::new_with_options(
+                dir_entry.path(),
+                Some(parent.clone()),
+                self.depth + 1,
+                options,
+            ) {
+                Ok(entry) if options.exclude.matches(&entry) => {}
+                Ok(Entry::File(_)) if skips_content(&dir_entry.path(), options) => {}
+                Ok(mut entry) => {
+                    if let Entry::Dir(dir) = &mut entry {
+                        dir.mount_point =
+                            options.detect_mounts && crosses_device(self.dev, dir.dev);
+                    }
+                    if entry.is_reserved_name() {
+                        // Kept, but flagged since it will trip up Windows.
+                        errors.push(path_error(entry.get_path(), "name is reserved on Windows"));
+                    }
+                    self.children.push(entry.get_format_path());
+                    on_child(entry);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if let Some(started) = started {
+            self.read_time = Some(options.read_timer.read_time(&self.path, started.elapsed()));
+        }
+        errors
+    }
+
+    /// Loads only the direct children of this directory into `storage`.
+    pub fn load_children_with_storage(
+        &mut self,
+        storage: &dyn Storage<String, Entry>,
+        options: &ScanOptions,
+    ) -> Vec<GenericError> {
+        self.get_load_children(options, |child| storage.set(child.get_format_path(), child))
+    }
+
+    /// Recursively loads every entry below this directory in parallel,
+    /// placing them into `storage` if one is given.
+    ///
+    /// The directory itself is not stored, that is left to the caller.
+    pub fn load_all_children_with_storage(
+        &mut self,
+        storage: Option<Arc<dyn Storage<String, Entry>>>,
+        options: &ScanOptions,
+    ) -> Vec<GenericError> {
+        let errors = ErrorCollector::new(options.max_errors);
+        self.load_all_children_with_error_sink(storage, options, &|e| errors.push(e));
+        errors.into_parts().0
+    }
+
+    /// Same as `load_all_children_with_storage`, but hands every error to
+    /// `on_error` as it happens instead of collecting them.
+    ///
+    /// Returns whether directories were left unread because the scan went
+    /// over `options.max_bytes`.
+    pub fn load_all_children_with_error_sink(
+        &mut self,
+        storage: Option<Arc<dyn Storage<String, Entry>>>,
+        options: &ScanOptions,
+        on_error: &(dyn Fn(GenericError) + Sync),
+    ) -> bool {
+        self.load_all_children_with(
+            options,
+            |entry| {
+                if let Some(storage) = &storage {
+                    storage.set(entry.get_format_path(), entry);
+                }
+            },
+            on_error,
+        )
+    }
+
+    /// Recursively walks every entry below this directory in parallel and
+    /// calls `visitor` once for each, without storing anything.
+    ///
+    /// `visitor` runs concurrently on the worker threads, so it has to be
+    /// `Fn + Sync`; wrap mutable state in an atomic or a `Mutex`.
+    pub fn visit_all_children<F>(&mut self, options: &ScanOptions, visitor: F) -> Vec<GenericError>
+    where
+        F: Fn(&Entry) + Sync,
+    {
+        let errors = ErrorCollector::new(options.max_errors);
+        self.load_all_children_with(options, |entry| visitor(&entry), &|e| errors.push(e));
+        errors.into_parts().0
+    }
+
+    /// Estimates the size and entry count below this directory by reading
+    /// only about `fraction` of its subdirectories, each at any depth, and
+    /// scaling what was read by how likely it was to be picked. Direct
+    /// children are always read. Runs on the calling thread.
+    pub fn sample_all_children(
+        &mut self,
+        options: &ScanOptions,
+        fraction: f64,
+    ) -> (ApproxResult, Vec<GenericError>) {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let mut pending = vec![];
+        let mut errors = self.get_load_children(options, |child| pending.push((child, 1.0)));
+
+        let (mut size, mut entries, mut read) = (0.0, 0.0, 0u64);
+        while let Some((entry, weight)) = pending.pop() {
+            read += 1;
+            entries += weight;
+            size += entry.get_size() as f64 * weight;
+            if let Entry::Dir(mut dir) = entry {
+                if sample::is_sampled(&dir.get_format_path(), fraction) {
+                    let weight = weight / fraction;
+                    errors.extend(
+                        dir.get_load_children(options, |child| pending.push((child, weight))),
+                    );
+                }
+            }
+        }
+
+        let result = ApproxResult {
+            estimate: size.round() as u64,
+            entries: entries.round() as u64,
+            sampled_fraction: if entries > 0.0 {
+                read as f64 / entries
+            } else {
+                1.0
+            },
+        };
+        (result, errors)
+    }
+
+    /// Recursively walks every entry below this directory in parallel,
+    /// reporting each directory listed, file found and error hit to
+    /// `on_event` as it happens. This directory is reported too. Nothing is
+    /// stored.
+    pub fn walk_all_children<F>(&mut self, options: &ScanOptions, on_event: F)
+    where
+        F: Fn(WalkEvent) + Sync,
+    {
+        let list = |dir: &mut DirEntry, push: &mut dyn FnMut(Entry)| {
+            on_event(WalkEvent::EnteredDir {
+                path: dir.get_format_path(),
+                depth: dir.get_depth(),
+            });
+            for e in dir.get_load_children(options, &mut *push) {
+                on_event(WalkEvent::Error(e));
+            }
+            on_event(WalkEvent::FinishedDir {
+                path: dir.get_format_path(),
+                children: dir.get_children().len(),
+            });
+        };
+
+        let mut children = vec![];
+        list(self, &mut |child| children.push(child));
+        let panics = run_parallel(options, children, |entry: Entry, push| match entry {
+            Entry::Dir(mut dir) => list(&mut dir, push),
+            Entry::File(file) => on_event(WalkEvent::FoundFile {
+                path: file.get_format_path(),
+                depth: file.get_depth(),
+                size: file.get_size(),
+                modified: file.get_modified(),
+            }),
+        });
+        for e in panics {
+            on_event(WalkEvent::Error(e));
+        }
+    }
+
+    /// Recursive size of each direct child, found by walking each child's
+    /// subtree in turn without storing anything.
+    pub fn child_subtree_sizes(
+        &mut self,
+        options: &ScanOptions,
+    ) -> (Vec<(String, u64)>, Vec<GenericError>) {
+        let mut children = vec![];
+        let mut errors = self.get_load_children(options, |child| children.push(child));
+
+        let mut sizes = vec![];
+        for child in children {
+            let key = child.get_format_path();
+            let size = match child {
+                Entry::File(file) => file.get_size(),
+                Entry::Dir(mut dir) => {
+                    let total = AtomicU64::new(0);
+                    errors.extend(dir.visit_all_children(options, |entry| {
+                        total.fetch_add(entry.get_size(), Ordering::Relaxed);
+                    }));
+                    total.into_inner()
+                }
+            };
+            sizes.push((key, size));
+        }
+
+        (sizes, errors)
+    }
+
+    /// Same as `child_subtree_sizes`, but walks several child subtrees at
+    /// once, which pays off when a few huge children would otherwise hold up
+    /// the rest. The workers of `options` are split between the concurrent
+    /// walks, so no more than `thread_count()` run in total.
+    pub fn child_subtree_sizes_parallel(
+        &mut self,
+        options: &ScanOptions,
+    ) -> (Vec<(String, u64)>, Vec<GenericError>) {
+        let mut children = vec![];
+        let mut errors = self.get_load_children(options, |child| children.push(child));
+
+        let dirs = children.iter().filter(|child| child.is_dir()).count();
+        let walks = dirs.min(options.thread_count()).max(1);
+        let walk_options = ScanOptions {
+            threads: Some(options.thread_count() / walks),
+            ..options.clone()
+        };
+
+        let sizes: Vec<AtomicU64> = children.iter().map(|_| AtomicU64::new(0)).collect();
+        let next = AtomicUsize::new(0);
+        let walk_errors = Mutex::new(vec![]);
+        thread::scope(|scope| {
+            for _ in 0..walks {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let child = match children.get(i) {
+                        Some(child) => child,
+                        None => break,
+                    };
+                    match child {
+                        Entry::File(file) => sizes[i].store(file.get_size(), Ordering::Relaxed),
+                        Entry::Dir(dir) => {
+                            let found = dir.clone().visit_all_children(&walk_options, |entry| {
+                                sizes[i].fetch_add(entry.get_size(), Ordering::Relaxed);
+                            });
+                            walk_errors.lock().unwrap().extend(found);
+                        }
+                    }
+                });
+            }
+        });
+        errors.extend(walk_errors.into_inner().unwrap());
+
+        let sizes = children
+            .iter()
+            .zip(sizes)
+            .map(|(child, size)| (child.get_format_path(), size.into_inner()))
+            .collect();
+        (sizes, errors)
+    }
+
+    /// Shared traversal behind the loading and visiting entry points, every
+    /// discovered entry is handed to `sink` once its own children are known.
+    fn load_all_children_with<F>(
+        &mut self,
+        options: &ScanOptions,
+        sink: F,
+        on_error: &(dyn Fn(GenericError) + Sync),
+    ) -> bool
+    where
+        F: Fn(Entry) + Sync,
+    {
+        // The root's children seed the queue, everything below is pushed
+        // straight onto the worker deques while it is being read.
+        let mut children = vec![];
+        if options.descends_into(self.depth) {
+            for e in self.get_load_children(options, |child| children.push(child)) {
+                on_error(e);
+            }
+        }
+
+        let total = AtomicU64::new(0);
+        let truncated = AtomicBool::new(false);
+        // Directories already read, so a symlink leading back up the tree
+        // isn't followed round and round.
+        let visited = Mutex::new(HashSet::new());
+        visited.lock().unwrap().extend(self.inode);
+        let panics = run_parallel(options, children, |mut entry: Entry, push| {
+            #[cfg(test)]
+            test_seam::maybe_panic(&entry);
+
+            // Only a byte budget needs the running total shared between workers.
+            let over_budget = options.max_bytes.is_some_and(|max| {
+                let size = entry.get_size();
+                total.fetch_add(size, Ordering::Relaxed) + size > max
+            });
+            if let Entry::Dir(ref mut dir) = entry {
+                if over_budget {
+                    truncated.store(true, Ordering::Relaxed);
+                } else if options.descends_into(dir.depth) {
+                    let first_visit = dir
+                        .inode
+                        .is_none_or(|inode| visited.lock().unwrap().insert(inode));
+                    if first_visit {
+                        for e in dir.get_load_children(options, &mut *push) {
+                            on_error(e);
+                        }
+                    } else {
+                        on_error(path_error(
+                            dir.get_path(),
+                            "directory was already scanned through another path, skipping \
+                             possible symlink cycle",
+                        ));
+                    }
+                }
+            }
+            sink(entry);
+        });
+
+        for e in panics {
+            on_error(e);
+        }
+        truncated.into_inner()
+    }
+
+    /// Counts every entry below this directory, walking `storage` on a single thread.
+    pub fn count_all_children(&self, storage: &dyn Storage<String, Entry>) -> usize {
+        let mut count = 0;
+        let mut stack = self.children.clone();
+        while let Some(key) = stack.pop() {
+            count += 1;
+            if let Some(Entry::Dir(dir)) = storage.get(&key) {
+                stack.extend(dir.children);
+            }
+        }
+        count
+    }
+
+    /// Same as `count_all_children`, but spreads the walk over all cpus.
+    ///
+    /// Fails if a worker panicked, since the count would be incomplete.
+    pub fn count_entries_multi(
+        &self,
+        storage: &dyn Storage<String, Entry>,
+    ) -> Result<usize, GenericError> {
+        let (counts, mut panics) = run_parallel_with_state(
+            &ScanOptions::default(),
+            self.children.clone(),
+            || 0,
+            |key: String, count: &mut usize, push| {
+                *count += 1;
+                if let Some(Entry::Dir(dir)) = storage.get(&key) {
+                    for child in dir.children {
+                        push(child);
+                    }
+                }
+            },
+        );
+        match panics.pop() {
+            Some(e) => Err(e),
+            None => Ok(counts.into_iter().sum()),
+        }
+    }
+
+    /// Sums file sizes below this directory, walking `storage` on a single thread.
+    pub fn calculate_size_all_children(&self, storage: &dyn Storage<String, Entry>) -> u64 {
+        let mut size = 0;
+        let mut stack = self.children.clone();
+        while let Some(key) = stack.pop() {
+            match storage.get(&key) {
+                Some(Entry::Dir(dir)) => stack.extend(dir.children),
+                Some(Entry::File(file)) => size += file.get_size(),
+                None => {}
+            }
+        }
+        size
+    }
+
+    /// Same as `calculate_size_all_children`, but spreads the walk over all cpus.
+    ///
+    /// Fails if a worker panicked, since the sum would be incomplete.
+    pub fn calculate_size_all_children_multi(
+        &self,
+        storage: &dyn Storage<String, Entry>,
+    ) -> Result<u64, GenericError> {
+        let (sizes, mut panics) = run_parallel_with_state(
+            &ScanOptions::default(),
+            self.children.clone(),
+            || 0,
+            |key: String, size: &mut u64, push| match storage.get(&key) {
+                Some(Entry::Dir(dir)) => {
+                    for child in dir.children {
+                        push(child);
+                    }
+                }
+                Some(Entry::File(file)) => *size += file.get_size(),
+                None => {}
+            },
+        );
+        match panics.pop() {
+            Some(e) => Err(e),
+            None => Ok(sizes.into_iter().sum()),
+        }
+    }
+}
+
+/// Whether a directory on `dev` is on another device than its parent on
+/// `parent_dev`. Unknown devices never count as a boundary.
+fn crosses_device(parent_dev: Option<u64>, dev: Option<u64>) -> bool {
+    match (parent_dev, dev) {
+        (Some(parent_dev), Some(dev)) => parent_dev != dev,
+        _ => false,
+    }
+}
+
+fn skips_content(p: &Path, options: &ScanOptions) -> bool {
+    !options.skip_content.is_empty()
+        && content::detect(p).is_some_and(|kind| options.skip_content.contains(&kind))
+}
+
+#[cfg(test)]
+pub(crate) mod test_seam {
+    use super::Entry;
+
+    /// Entries with this name make the scan worker panic.
+    pub const PANIC_NAME: &str = "drfs-test-panic";
+
+    pub fn maybe_panic(entry: &Entry) {
+        if entry.get_name() == PANIC_NAME {
+            panic!("injected panic for {}", entry.get_format_path());
+        }
+    }
+
+    /// Directories named with this prefix fail to read as if permission was
+    /// denied, which can't be arranged with file modes when tests run as root.
+    pub const DENIED_PREFIX: &str = "drfs-test-denied";
+
+    pub fn maybe_deny(name: &str) -> std::io::Result<()> {
+        if name.starts_with(DENIED_PREFIX) {
+            return Err(std::io::ErrorKind::PermissionDenied.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "content-type")]
+    use crate::entry::ContentKind;
+    use crate::store::memstorage::MemStorage;
+    use crate::test_util::write_file;
+
+    #[test]
+    fn worker_panic_is_reported_as_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "sub/b.txt", 1);
+        write_file(tmp.path(), test_seam::PANIC_NAME, 1);
+
+        let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+        let mut root = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let errors =
+            root.load_all_children_with_storage(Some(storage.clone()), &ScanOptions::default());
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("panicked"));
+        // Everything but the poisoned entry still made it into storage.
+        assert_eq!(storage.len(), 3);
+    }
+
+    #[test]
+    fn max_depth_stops_descending() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "top.txt", 1);
+        write_file(tmp.path(), "a/mid.txt", 1);
+        write_file(tmp.path(), "a/b/low.txt", 1);
+        write_file(tmp.path(), "a/b/c/lowest.txt", 1);
+
+        let scan = |max_depth| {
+            let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+            let mut root = match Entry::new(tmp.path()).unwrap() {
+                Entry::Dir(dir) => dir,
+                Entry::File(_) => unreachable!(),
+            };
+            let options = ScanOptions {
+                max_depth,
+                threads: Some(2),
+                ..ScanOptions::default()
+            };
+            assert!(root
+                .load_all_children_with_storage(Some(storage.clone()), &options)
+                .is_empty());
+            let mut depths: Vec<_> = storage.values().iter().map(Entry::get_depth).collect();
+            depths.sort();
+            (root.get_children().len(), depths)
+        };
+
+        assert_eq!(scan(Some(0)), (0, vec![]));
+        assert_eq!(scan(Some(1)), (2, vec![1, 1]));
+        assert_eq!(scan(Some(2)), (2, vec![1, 1, 2, 2]));
+        assert_eq!(scan(None), (2, vec![1, 1, 2, 2, 3, 3, 4]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_only_followed_when_asked() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = write_file(tmp.path(), "outside/a.bin", 100);
+        let tree = tmp.path().join("tree");
+        fs::create_dir(&tree).unwrap();
+        std::os::unix::fs::symlink(outside.parent().unwrap(), tree.join("link")).unwrap();
+        std::os::unix::fs::symlink("../outside/a.bin", tree.join("file-link")).unwrap();
+
+        let scan = |follow_symlinks| {
+            let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+            let mut root = match Entry::new(&tree).unwrap() {
+                Entry::Dir(dir) => dir,
+                Entry::File(_) => unreachable!(),
+            };
+            let options = ScanOptions {
+                follow_symlinks,
+                ..ScanOptions::default()
+            };
+            assert!(root
+                .load_all_children_with_storage(Some(storage.clone()), &options)
+                .is_empty());
+            storage
+        };
+        let root = format_path(&tree);
+
+        let kept = scan(false);
+        assert_eq!(kept.len(), 2);
+        let link = kept.get(&format!("{}/link", root)).unwrap();
+        assert!(link.is_symlink() && !link.is_dir());
+        let file_link = kept.get(&format!("{}/file-link", root)).unwrap();
+        assert!(file_link.is_symlink());
+        assert_eq!(file_link.get_size(), "../outside/a.bin".len() as u64);
+
+        let followed = scan(true);
+        assert_eq!(followed.len(), 3);
+        assert!(followed.get(&format!("{}/link/a.bin", root)).is_some());
+        assert_eq!(
+            followed
+                .get(&format!("{}/file-link", root))
+                .unwrap()
+                .get_size(),
+            100
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycles_are_skipped() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a/b/file", 5);
+        std::os::unix::fs::symlink("..", tmp.path().join("a/b/up")).unwrap();
+        std::os::unix::fs::symlink(tmp.path(), tmp.path().join("a/root")).unwrap();
+
+        let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+        let mut root = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+        let errors = root.load_all_children_with_storage(Some(storage.clone()), &options);
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .all(|e| e.to_string().contains("symlink cycle")));
+        // a, a/b, a/b/file and both links, which are kept without children.
+        assert_eq!(storage.len(), 5);
+        let up = format!("{}/a/b/up", format_path(tmp.path()));
+        match storage.get(&up) {
+            Some(Entry::Dir(dir)) => assert!(dir.get_children().is_empty()),
+            other => panic!("expected a directory, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn per_worker_totals_match_single_threaded_walk() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..30 {
+            for j in 0..7 {
+                write_file(tmp.path(), &format!("d{}/e{}/f{}", i % 6, i, j), i + j);
+            }
+        }
+
+        let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+        let mut root = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        root.load_all_children_with_storage(Some(storage.clone()), &ScanOptions::default());
+
+        let count = root.count_all_children(storage.as_ref());
+        assert_eq!(count, 6 + 30 + 30 * 7);
+        assert_eq!(root.count_entries_multi(storage.as_ref()).unwrap(), count);
+        assert_eq!(
+            root.calculate_size_all_children_multi(storage.as_ref())
+                .unwrap(),
+            root.calculate_size_all_children(storage.as_ref())
+        );
+    }
+
+    #[test]
+    fn wide_directory_children_share_parent() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            write_file(tmp.path(), &format!("f{}", i), 1);
+        }
+
+        let mut root = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let mut children = vec![];
+        let errors = root.get_load_children(&ScanOptions::default(), |child| children.push(child));
+        assert!(errors.is_empty());
+        assert_eq!(children.len(), 200);
+
+        let parent = root.get_format_path();
+        for child in &children {
+            assert_eq!(child.get_parent(), Some(parent.clone()));
+        }
+    }
+
+    #[test]
+    fn large_directory_streams_children() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..2000 {
+            write_file(tmp.path(), &format!("sub/f{}", i), 1);
+        }
+
+        let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+        let mut root = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        assert!(root
+            .load_all_children_with_storage(Some(storage.clone()), &ScanOptions::default())
+            .is_empty());
+
+        let sub = match storage.get(&root.get_children()[0]).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let mut expected: Vec<String> = fs::read_dir(tmp.path().join("sub"))
+            .unwrap()
+            .map(|e| format_path(&e.unwrap().path()))
+            .collect();
+        let mut loaded = sub.get_children().to_vec();
+        expected.sort();
+        loaded.sort();
+        assert_eq!(loaded, expected);
+        assert_eq!(storage.len(), 2001);
+
+        // Children are handed out one by one while the directory is read.
+        let mut sub = sub;
+        let mut seen = 0;
+        let errors = sub.get_load_children(&ScanOptions::default(), |_| seen += 1);
+        assert!(errors.is_empty());
+        assert_eq!(seen, 2000);
+    }
+
+    #[test]
+    fn steal_batch_does_not_change_results() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            for j in 0..10 {
+                write_file(tmp.path(), &format!("d{}/e{}/f{}", i, j % 3, j), j);
+            }
+        }
+
+        let scan = |steal_batch| {
+            let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+            let mut root = match Entry::new(tmp.path()).unwrap() {
+                Entry::Dir(dir) => dir,
+                Entry::File(_) => unreachable!(),
+            };
+            let options = ScanOptions {
+                threads: Some(4),
+                steal_batch,
+                ..ScanOptions::default()
+            };
+            assert!(root
+                .load_all_children_with_storage(Some(storage.clone()), &options)
+                .is_empty());
+            let mut keys = storage.keys();
+            keys.sort();
+            let size: u64 = storage.values().iter().map(Entry::get_size).sum();
+            (keys, size)
+        };
+
+        let expected = scan(None);
+        assert_eq!(expected.0.len(), 20 + 60 + 200);
+        for steal_batch in [Some(0), Some(1), Some(4), Some(64)] {
+            assert_eq!(scan(steal_batch), expected);
+        }
+    }
+
+    #[cfg(feature = "content-type")]
+    #[test]
+    fn skipped_content_is_left_out() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(
+            tmp.path().join("picture.dat"),
+            b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR",
+        )
+        .unwrap();
+        write_file(tmp.path(), "notes.txt", 5);
+
+        let scan = |skip_content| {
+            let storage: Arc<MemStorage<String, Entry>> = Arc::new(MemStorage::new());
+            let mut root = match Entry::new(tmp.path()).unwrap() {
+                Entry::Dir(dir) => dir,
+                Entry::File(_) => unreachable!(),
+            };
+            let options = ScanOptions {
+                skip_content,
+                ..ScanOptions::default()
+            };
+            assert!(root
+                .load_all_children_with_storage(Some(storage.clone()), &options)
+                .is_empty());
+            (root.get_children().len(), storage.len())
+        };
+
+        assert_eq!(scan(vec![]), (2, 2));
+        assert_eq!(scan(vec![ContentKind::Video]), (2, 2));
+        assert_eq!(scan(vec![ContentKind::Image]), (1, 1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collected_errors_are_capped() {
+        let tmp = tempfile::tempdir().unwrap();
+        // Dangling symlinks fail to stat when followed, even when running as root.
+        for i in 0..10 {
+            std::os::unix::fs::symlink(
+                tmp.path().join("missing"),
+                tmp.path().join(format!("dangling{}", i)),
+            )
+            .unwrap();
+        }
+
+        let mut root = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let options = ScanOptions {
+            max_errors: Some(3),
+            follow_symlinks: true,
+            ..ScanOptions::default()
+        };
+        let errors = ErrorCollector::new(options.max_errors);
+        root.load_all_children_with_error_sink(None, &options, &|e| errors.push(e));
+        let (errors, truncated) = errors.into_parts();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(truncated, 7);
+
+        assert_eq!(root.load_all_children_with_storage(None, &options).len(), 3);
+    }
+
+    #[test]
+    fn mount_points_differ_in_device_from_parent() {
+        assert!(crosses_device(Some(1), Some(2)));
+        assert!(!crosses_device(Some(1), Some(1)));
+        assert!(!crosses_device(None, Some(2)));
+        assert!(!crosses_device(Some(1), None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mounted_directories_are_flagged() {
+        use std::os::unix::fs::MetadataExt;
+
+        // Needs something mounted right below `/`, e.g. `/proc`.
+        let root_dev = fs::metadata("/").unwrap().dev();
+        let mounted: Vec<PathBuf> = fs::read_dir("/")
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|child| {
+                fs::symlink_metadata(child.path()).is_ok_and(|m| m.is_dir() && m.dev() != root_dev)
+            })
+            .map(|child| child.path())
+            .collect();
+        if mounted.is_empty() {
+            return;
+        }
+
+        let mut root = match Entry::new("/").unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let storage = MemStorage::new();
+        let options = ScanOptions {
+            detect_mounts: true,
+            ..ScanOptions::default()
+        };
+        root.load_children_with_storage(&storage, &options);
+        for path in mounted {
+            let entry = storage.get(&format_path(&path)).unwrap();
+            assert!(entry.is_mount_point(), "{}", path.display());
+        }
+
+        let storage = MemStorage::new();
+        root.load_children_with_storage(&storage, &ScanOptions::default());
+        assert!(storage.values().iter().all(|entry| !entry.is_mount_point()));
+    }
+}
@@ -0,0 +1,26 @@
+use std::time::SystemTime;
+
+use crate::GenericError;
+
+/// Progress of a walk, see `EntryWrapper::walk_events`. Events from
+/// different directories interleave since they come from several workers.
+#[derive(Debug)]
+pub enum WalkEvent {
+    /// About to list a directory.
+    EnteredDir {
+        path: String,
+        depth: usize,
+    },
+    FoundFile {
+        path: String,
+        depth: usize,
+        size: u64,
+        modified: Option<SystemTime>,
+    },
+    Error(GenericError),
+    /// Done listing a directory, its subdirectories may still be pending.
+    FinishedDir {
+        path: String,
+        children: usize,
+    },
+}
@@ -0,0 +1,375 @@
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    format_path, gid, inode, mime, mode, path_name, read_times, time_errors, uid, FileCategory,
+    ScanOptions,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    path: Box<PathBuf>,
+    name: String,
+    extension: Option<String>,
+    parent: Option<Arc<str>>,
+    depth: usize,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mode: Option<u32>,
+    readonly: bool,
+    size: u64,
+    allocated: u64,
+    inode: Option<(u64, u64)>,
+    executable: bool,
+    symlink: bool,
+    #[serde(with = "super::time_serde::result")]
+    accessed: Result<SystemTime, Arc<io::Error>>,
+    #[serde(with = "super::time_serde::result")]
+    modified: Result<SystemTime, Arc<io::Error>>,
+    #[serde(with = "super::time_serde::result")]
+    created: Result<SystemTime, Arc<io::Error>>,
+    #[serde(with = "super::time_serde")]
+    scanned_at: SystemTime,
+}
+
+impl FileEntry {
+    pub fn new<P: AsRef<Path>>(
+        p: P,
+        parent: Option<Arc<str>>,
+        depth: usize,
+        metadata: &Metadata,
+        options: &ScanOptions,
+    ) -> FileEntry {
+        let p = p.as_ref();
+        let (accessed, modified, created) = read_times(metadata, options.collect_times);
+        let symlink = metadata.file_type().is_symlink();
+        FileEntry {
+            path: Box::new(p.to_path_buf()),
+            name: path_name(p),
+            extension: p.extension().map(|ext| ext.to_string_lossy().into_owned()),
+            parent,
+            depth,
+            uid: uid(metadata),
+            gid: gid(metadata),
+            mode: mode(metadata),
+            readonly: metadata.permissions().readonly(),
+            size: file_size(p, metadata, options),
+            allocated: allocated_size(metadata),
+            inode: inode(metadata),
+            // Links always carry every permission bit, whatever they point to.
+            executable: !symlink && is_executable(p, metadata),
+            symlink,
+            accessed,
+            modified,
+            created,
+            scanned_at: SystemTime::now(),
+        }
+    }
+
+    pub fn get_format_path(&self) -> String {
+        format_path(&self.path)
+    }
+
+    /// The path as scanned, without the lossy conversion of `get_format_path`.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Extension as found in the name, without the dot. `None` for names
+    /// without one, like `Makefile` or `.bashrc`.
+    pub fn get_extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    /// MIME type guessed from the extension, case-insensitively, `None` if
+    /// it isn't a known one. Looked up on each call, so scans don't pay for it.
+    pub fn get_mime(&self) -> Option<&str> {
+        mime::from_extension(&self.extension.as_deref()?.to_lowercase())
+    }
+
+    pub fn get_parent(&self) -> Option<String> {
+        self.parent.as_deref().map(str::to_string)
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Bytes actually allocated on disk, which is less than `get_size` for
+    /// sparse files. Same as `get_size` where block counts are unavailable.
+    pub fn get_allocated_size(&self) -> u64 {
+        self.allocated
+    }
+
+    /// `(device, inode)` identifying the file on disk, shared by hardlinks.
+    pub fn get_inode(&self) -> Option<(u64, u64)> {
+        self.inode
+    }
+
+    /// Any execute bit is set on Unix, an executable extension on Windows.
+    pub fn is_executable(&self) -> bool {
+        self.executable
+    }
+
+    /// Whether this entry is a symlink itself rather than what it points to,
+    /// its size being the length of the target path.
+    pub fn is_symlink(&self) -> bool {
+        self.symlink
+    }
+
+    /// Size of the file on disk now, read the same way the scan did: a
+    /// symlink by its own size, a block device by its capacity when
+    /// `options.device_sizes` is set.
+    pub(crate) fn current_size(&self, options: &ScanOptions) -> io::Result<u64> {
+        let metadata = if self.symlink {
+            fs::symlink_metadata(self.get_path())?
+        } else {
+            fs::metadata(self.get_path())?
+        };
+        Ok(file_size(self.get_path(), &metadata, options))
+    }
+
+    /// Coarse content category guessed from the extension, case-insensitively.
+    pub fn category(&self) -> FileCategory {
+        self.extension
+            .as_deref()
+            .map(|ext| FileCategory::from_extension(&ext.to_lowercase()))
+            .unwrap_or(FileCategory::Other)
+    }
+
+    /// Owning user id, `None` on platforms without Unix ownership.
+    pub fn get_uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// Owning group id, `None` on platforms without Unix ownership.
+    pub fn get_gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// Unix mode bits, permissions and file type, `None` on other platforms.
+    pub fn get_mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Whether the read-only attribute is set, on Unix whether no write bit is set.
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    pub fn get_accessed(&self) -> Option<SystemTime> {
+        self.accessed.as_ref().ok().copied()
+    }
+
+    pub fn get_modified(&self) -> Option<SystemTime> {
+        self.modified.as_ref().ok().copied()
+    }
+
+    pub fn get_created(&self) -> Option<SystemTime> {
+        self.created.as_ref().ok().copied()
+    }
+
+    /// Why the time getters return `None`, one `"<field>: <error>"` line per
+    /// time that couldn't be read.
+    pub fn get_time_errors(&self) -> Vec<String> {
+        time_errors(&self.accessed, &self.modified, &self.created)
+    }
+
+    /// Wall-clock time at which this entry was read from disk.
+    pub fn get_scanned_at(&self) -> SystemTime {
+        self.scanned_at
+    }
+}
+
+/// Where the size of a file comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SizeSource {
+    Metadata,
+    /// Block devices report 0 in their metadata, their capacity has to be
+    /// asked from the device itself.
+    BlockDevice,
+}
+
+fn size_source(metadata: &Metadata, options: &ScanOptions) -> SizeSource {
+    if options.device_sizes && is_block_device(metadata) {
+        SizeSource::BlockDevice
+    } else {
+        SizeSource::Metadata
+    }
+}
+
+/// Falls back to the metadata length if the device can't be queried, e.g.
+/// for lack of permissions or support.
+fn file_size(p: &Path, metadata: &Metadata, options: &ScanOptions) -> u64 {
+    match size_source(metadata, options) {
+        SizeSource::Metadata => metadata.len(),
+        SizeSource::BlockDevice => block_device_size(p).unwrap_or(metadata.len()),
+    }
+}
+
+#[cfg(unix)]
+fn is_block_device(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    metadata.file_type().is_block_device()
+}
+
+#[cfg(not(unix))]
+fn is_block_device(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Capacity of the block device at `p` in bytes.
+#[cfg(all(feature = "device-size", target_os = "linux"))]
+fn block_device_size(p: &Path) -> io::Result<u64> {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+
+    // _IOR(0x12, 114, size_t)
+    const BLKGETSIZE64: libc::c_ulong = 0x8008_1272;
+
+    let device = File::open(p)?;
+    let mut size: u64 = 0;
+    if unsafe { libc::ioctl(device.as_raw_fd(), BLKGETSIZE64, &mut size) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(size)
+}
+
+#[cfg(not(all(feature = "device-size", target_os = "linux")))]
+fn block_device_size(_p: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "querying device sizes is not supported on this build",
+    ))
+}
+
+#[cfg(unix)]
+fn allocated_size(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks is always in 512 byte units regardless of the fs block size.
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn allocated_size(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn is_executable(_p: &Path, metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(windows)]
+fn is_executable(p: &Path, _metadata: &Metadata) -> bool {
+    const EXECUTABLE_EXTENSIONS: [&str; 3] = ["exe", "bat", "cmd"];
+
+    p.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| EXECUTABLE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_executable(_p: &Path, _metadata: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::Entry;
+    use crate::test_util::write_file;
+
+    #[test]
+    fn regular_files_use_metadata_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "a.bin", 42);
+        let metadata = std::fs::metadata(&path).unwrap();
+        let options = ScanOptions {
+            device_sizes: true,
+            ..ScanOptions::default()
+        };
+
+        assert_eq!(size_source(&metadata, &options), SizeSource::Metadata);
+        assert_eq!(file_size(&path, &metadata, &options), 42);
+    }
+
+    #[test]
+    fn extension_is_parsed_from_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let extension = |name: &str| {
+            let path = write_file(tmp.path(), name, 1);
+            let metadata = std::fs::metadata(&path).unwrap();
+            FileEntry::new(&path, None, 0, &metadata, &ScanOptions::default())
+                .get_extension()
+                .map(str::to_string)
+        };
+
+        assert_eq!(extension("notes.txt").as_deref(), Some("txt"));
+        assert_eq!(extension("archive.tar.GZ").as_deref(), Some("GZ"));
+        assert_eq!(extension("Makefile"), None);
+        assert_eq!(extension(".bashrc"), None);
+        assert_eq!(extension(".config.toml").as_deref(), Some("toml"));
+    }
+
+    #[test]
+    fn mime_is_guessed_from_extension() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mime = |name: &str| {
+            Entry::new(write_file(tmp.path(), name, 1))
+                .unwrap()
+                .get_mime()
+                .map(str::to_string)
+        };
+
+        assert_eq!(mime("notes.txt").as_deref(), Some("text/plain"));
+        assert_eq!(mime("PHOTO.JPG").as_deref(), Some("image/jpeg"));
+        assert_eq!(mime("data.unknownext"), None);
+        assert_eq!(mime("Makefile"), None);
+        assert_eq!(Entry::new(tmp.path()).unwrap().get_mime(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn block_devices_are_routed_to_device_query() {
+        // Needs some block device to exist, which containers often lack.
+        let device = match std::fs::read_dir("/dev").ok().and_then(|entries| {
+            entries
+                .flatten()
+                .find(|e| e.metadata().is_ok_and(|m| is_block_device(&m)))
+        }) {
+            Some(device) => device.path(),
+            None => return,
+        };
+        let metadata = std::fs::metadata(&device).unwrap();
+
+        let enabled = ScanOptions {
+            device_sizes: true,
+            ..ScanOptions::default()
+        };
+        assert_eq!(size_source(&metadata, &enabled), SizeSource::BlockDevice);
+        assert_eq!(
+            size_source(&metadata, &ScanOptions::default()),
+            SizeSource::Metadata
+        );
+        // Whether or not the query is permitted, sizing must not fail.
+        let _ = file_size(&device, &metadata, &enabled);
+    }
+}
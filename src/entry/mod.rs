@@ -0,0 +1,726 @@
+mod category;
+mod content;
+mod dir;
+mod event;
+mod file;
+mod mime;
+mod options;
+mod owner;
+mod sample;
+mod skeleton;
+mod time_serde;
+mod topology;
+mod trie;
+mod wrapper;
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+pub use self::category::FileCategory;
+pub use self::content::ContentKind;
+pub use self::dir::DirEntry;
+pub use self::event::WalkEvent;
+pub use self::file::FileEntry;
+pub use self::options::{ScanOptions, CONFIG_FILE};
+pub use self::sample::ApproxResult;
+pub use self::skeleton::{DirSkeleton, DirSummary};
+pub use self::topology::TopologyIndex;
+pub use self::trie::PathTrie;
+pub use self::wrapper::{
+    ChildInfo, ChildKey, EntryWrapper, PermissionAudit, ScanReport, SizeChange, SizeOverview,
+    SizeTiers, TreeChange,
+};
+
+use crate::GenericError;
+
+/// How entry paths are rendered in reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathDisplay {
+    #[default]
+    Absolute,
+    /// Relative to the scan root, the root itself renders as `.`.
+    RelativeToRoot,
+    Basename,
+}
+
+impl FromStr for PathDisplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PathDisplay, String> {
+        match s {
+            "absolute" => Ok(PathDisplay::Absolute),
+            "relative" => Ok(PathDisplay::RelativeToRoot),
+            "basename" => Ok(PathDisplay::Basename),
+            _ => Err(format!(
+                "unknown path display '{}', expected absolute, relative or basename",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Entry {
+    File(FileEntry),
+    Dir(DirEntry),
+}
+
+impl Entry {
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Entry, GenericError> {
+        Entry::new_with_parent(p, None, 0)
+    }
+
+    pub fn new_with_parent<P: AsRef<Path>>(
+        p: P,
+        parent: Option<Arc<str>>,
+        depth: usize,
+    ) -> Result<Entry, GenericError> {
+        Entry::new_with_options(p, parent, depth, &ScanOptions::default())
+    }
+
+    /// Reads the entry at `p`. A symlink is kept as a file entry of its own,
+    /// see `FileEntry::is_symlink`, unless `options.follow_symlinks` is set
+    /// or it is at depth 0, so a scan root given as a symlink still works.
+    pub fn new_with_options<P: AsRef<Path>>(
+        p: P,
+        parent: Option<Arc<str>>,
+        depth: usize,
+        options: &ScanOptions,
+    ) -> Result<Entry, GenericError> {
+        let p = p.as_ref();
+        let metadata = if options.follow_symlinks || depth == 0 {
+            fs::metadata(p)
+        } else {
+            fs::symlink_metadata(p)
+        };
+        let metadata = metadata.map_err(|e| path_error(p, e))?;
+        Ok(Entry::from_metadata_with_options(
+            p, parent, depth, &metadata, options,
+        ))
+    }
+
+    /// Builds an entry from metadata the caller already has, e.g. from its
+    /// own directory walk, without touching the file system again. `depth`
+    /// is relative to whatever the caller treats as the root.
+    pub fn from_metadata<P: AsRef<Path>>(
+        p: P,
+        parent: Option<Arc<str>>,
+        depth: usize,
+        metadata: &Metadata,
+    ) -> Entry {
+        Entry::from_metadata_with_options(p, parent, depth, metadata, &ScanOptions::default())
+    }
+
+    pub fn from_metadata_with_options<P: AsRef<Path>>(
+        p: P,
+        parent: Option<Arc<str>>,
+        depth: usize,
+        metadata: &Metadata,
+        options: &ScanOptions,
+    ) -> Entry {
+        if metadata.is_dir() {
+            Entry::Dir(DirEntry::new(p, parent, depth, metadata, options))
+        } else {
+            Entry::File(FileEntry::new(p, parent, depth, metadata, options))
+        }
+    }
+
+    pub fn get_format_path(&self) -> String {
+        match self {
+            Entry::File(file) => file.get_format_path(),
+            Entry::Dir(dir) => dir.get_format_path(),
+        }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        match self {
+            Entry::File(file) => file.get_path(),
+            Entry::Dir(dir) => dir.get_path(),
+        }
+    }
+
+    /// Path rendered according to `style`, `root` is the scan root's formatted path.
+    pub fn get_display_path(&self, style: PathDisplay, root: &str) -> String {
+        display_path(&self.get_format_path(), style, root)
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Entry::File(file) => file.get_name(),
+            Entry::Dir(dir) => dir.get_name(),
+        }
+    }
+
+    pub fn get_parent(&self) -> Option<String> {
+        match self {
+            Entry::File(file) => file.get_parent(),
+            Entry::Dir(dir) => dir.get_parent(),
+        }
+    }
+
+    pub fn get_depth(&self) -> usize {
+        match self {
+            Entry::File(file) => file.get_depth(),
+            Entry::Dir(dir) => dir.get_depth(),
+        }
+    }
+
+    /// Size of a file in bytes, directories report 0.
+    pub fn get_size(&self) -> u64 {
+        match self {
+            Entry::File(file) => file.get_size(),
+            Entry::Dir(_) => 0,
+        }
+    }
+
+    pub fn get_uid(&self) -> Option<u32> {
+        match self {
+            Entry::File(file) => file.get_uid(),
+            Entry::Dir(dir) => dir.get_uid(),
+        }
+    }
+
+    pub fn get_gid(&self) -> Option<u32> {
+        match self {
+            Entry::File(file) => file.get_gid(),
+            Entry::Dir(dir) => dir.get_gid(),
+        }
+    }
+
+    /// Unix mode bits, permissions and file type, `None` on other platforms.
+    pub fn get_mode(&self) -> Option<u32> {
+        match self {
+            Entry::File(file) => file.get_mode(),
+            Entry::Dir(dir) => dir.get_mode(),
+        }
+    }
+
+    /// Whether anyone may write to the entry according to its Unix mode.
+    /// Never true for symlinks, whose mode always allows everything, nor on
+    /// platforms without Unix modes.
+    pub fn is_world_writable(&self) -> bool {
+        !self.is_symlink() && self.get_mode().is_some_and(|mode| mode & 0o002 != 0)
+    }
+
+    /// Whether the read-only attribute is set, on Unix whether no write bit is set.
+    pub fn is_readonly(&self) -> bool {
+        match self {
+            Entry::File(file) => file.is_readonly(),
+            Entry::Dir(dir) => dir.is_readonly(),
+        }
+    }
+
+    /// Name of the owning user from `/etc/passwd`, `None` if it isn't listed
+    /// there or on platforms without Unix ownership.
+    pub fn get_owner_name(&self) -> Option<String> {
+        self.get_uid().and_then(owner::user_name)
+    }
+
+    /// Name of the owning group from `/etc/group`, see `get_owner_name`.
+    pub fn get_group_name(&self) -> Option<String> {
+        self.get_gid().and_then(owner::group_name)
+    }
+
+    /// Extension of a file's name, always `None` for directories.
+    pub fn get_extension(&self) -> Option<&str> {
+        match self {
+            Entry::File(file) => file.get_extension(),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    /// MIME type guessed from a file's extension, always `None` for directories.
+    pub fn get_mime(&self) -> Option<&str> {
+        match self {
+            Entry::File(file) => file.get_mime(),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    pub fn get_accessed(&self) -> Option<SystemTime> {
+        match self {
+            Entry::File(file) => file.get_accessed(),
+            Entry::Dir(dir) => dir.get_accessed(),
+        }
+    }
+
+    pub fn get_modified(&self) -> Option<SystemTime> {
+        match self {
+            Entry::File(file) => file.get_modified(),
+            Entry::Dir(dir) => dir.get_modified(),
+        }
+    }
+
+    pub fn get_created(&self) -> Option<SystemTime> {
+        match self {
+            Entry::File(file) => file.get_created(),
+            Entry::Dir(dir) => dir.get_created(),
+        }
+    }
+
+    pub fn get_time_errors(&self) -> Vec<String> {
+        match self {
+            Entry::File(file) => file.get_time_errors(),
+            Entry::Dir(dir) => dir.get_time_errors(),
+        }
+    }
+
+    pub fn get_scanned_at(&self) -> SystemTime {
+        match self {
+            Entry::File(file) => file.get_scanned_at(),
+            Entry::Dir(dir) => dir.get_scanned_at(),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        matches!(self, Entry::Dir(_))
+    }
+
+    /// Whether this is a symlink that wasn't followed, see `new_with_options`.
+    pub fn is_symlink(&self) -> bool {
+        match self {
+            Entry::File(file) => file.is_symlink(),
+            Entry::Dir(_) => false,
+        }
+    }
+
+    /// Whether the name is reserved or invalid on Windows, see `is_reserved_name`.
+    pub fn is_reserved_name(&self) -> bool {
+        is_reserved_name(self.get_name())
+    }
+
+    /// Whether this is a directory on another device than its parent, see
+    /// `DirEntry::is_mount_point`. Always `false` for files.
+    pub fn is_mount_point(&self) -> bool {
+        match self {
+            Entry::File(_) => false,
+            Entry::Dir(dir) => dir.is_mount_point(),
+        }
+    }
+}
+
+/// Collects scan errors up to an optional cap, only counting the ones past it.
+pub(crate) struct ErrorCollector {
+    cap: usize,
+    errors: Mutex<Vec<GenericError>>,
+    truncated: AtomicUsize,
+    /// Errors pushed for lack of permissions, truncated ones included.
+    denied: AtomicUsize,
+}
+
+impl ErrorCollector {
+    pub(crate) fn new(cap: Option<usize>) -> ErrorCollector {
+        ErrorCollector {
+            cap: cap.unwrap_or(usize::MAX),
+            errors: Mutex::new(vec![]),
+            truncated: AtomicUsize::new(0),
+            denied: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn push(&self, e: GenericError) {
+        if e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied)
+        {
+            self.denied.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut errors = self.errors.lock().unwrap();
+        if errors.len() < self.cap {
+            errors.push(e);
+        } else {
+            self.truncated.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Errors pushed so far that were caused by missing permissions.
+    pub(crate) fn permission_denied(&self) -> usize {
+        self.denied.load(Ordering::Relaxed)
+    }
+
+    /// The collected errors and how many were dropped past the cap.
+    pub(crate) fn into_parts(self) -> (Vec<GenericError>, usize) {
+        (
+            self.errors.into_inner().unwrap(),
+            self.truncated.into_inner(),
+        )
+    }
+}
+
+type EntryTime = Result<SystemTime, Arc<io::Error>>;
+
+/// Reads the accessed, modified and created times unless `collect` is false,
+/// in which case all three are a shared "not collected" error.
+fn read_times(metadata: &Metadata, collect: bool) -> (EntryTime, EntryTime, EntryTime) {
+    if !collect {
+        static NOT_COLLECTED: OnceLock<Arc<io::Error>> = OnceLock::new();
+        let error = NOT_COLLECTED.get_or_init(|| {
+            Arc::new(io::Error::other(
+                "timestamps were not collected for this scan",
+            ))
+        });
+        return (Err(error.clone()), Err(error.clone()), Err(error.clone()));
+    }
+
+    (
+        metadata.accessed().map_err(Arc::new),
+        metadata.modified().map_err(Arc::new),
+        metadata.created().map_err(Arc::new),
+    )
+}
+
+fn time_errors(accessed: &EntryTime, modified: &EntryTime, created: &EntryTime) -> Vec<String> {
+    [
+        ("accessed", accessed),
+        ("modified", modified),
+        ("created", created),
+    ]
+    .iter()
+    .filter_map(|(field, time)| {
+        time.as_ref()
+            .err()
+            .map(|e| format!("{}: {} ({:?})", field, e, e.kind()))
+    })
+    .collect()
+}
+
+#[cfg(unix)]
+fn uid(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.uid())
+}
+
+#[cfg(not(unix))]
+fn uid(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn mode(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.mode())
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn gid(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.gid())
+}
+
+#[cfg(not(unix))]
+fn gid(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn dev(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn dev(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+pub(crate) fn inode(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn inode(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Formats `p` for display and storage keys. On Windows the `\\?\` verbatim
+/// prefix that `canonicalize` adds is dropped, the entry keeps the original
+/// path for actual file system calls.
+fn format_path(p: &Path) -> String {
+    let formatted = p.to_string_lossy();
+    if cfg!(windows) {
+        strip_verbatim_prefix(&formatted)
+    } else {
+        formatted.into_owned()
+    }
+}
+
+/// `\\?\C:\dir` -> `C:\dir` and `\\?\UNC\server\share` -> `\\server\share`.
+fn strip_verbatim_prefix(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        rest.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Renders the formatted path `path` according to `style`.
+pub(crate) fn display_path(path: &str, style: PathDisplay, root: &str) -> String {
+    match style {
+        PathDisplay::Absolute => path.to_string(),
+        PathDisplay::RelativeToRoot => match Path::new(path).strip_prefix(root) {
+            Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+            Ok(rel) => format_path(rel),
+            Err(_) => path.to_string(),
+        },
+        PathDisplay::Basename => path_name(Path::new(path)),
+    }
+}
+
+fn path_name(p: &Path) -> String {
+    match p.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => format_path(p),
+    }
+}
+
+/// Whether `name` can't be used as is on Windows: a device name such as
+/// `CON` or `LPT1`, with or without an extension and in any case, or a name
+/// ending in a dot or a space. Such entries can be created elsewhere but
+/// break or misbehave when copied to or accessed from Windows.
+pub fn is_reserved_name(name: &str) -> bool {
+    const DEVICES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+    if name.ends_with('.') || name.ends_with(' ') {
+        return true;
+    }
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or(name)
+        .trim_end()
+        .to_uppercase();
+    if DEVICES.contains(&stem.as_str()) {
+        return true;
+    }
+    match stem
+        .strip_prefix("COM")
+        .or_else(|| stem.strip_prefix("LPT"))
+    {
+        Some(digit) => matches!(digit.as_bytes(), [b'1'..=b'9']),
+        None => false,
+    }
+}
+
+fn path_error<E: std::fmt::Display>(p: &Path, e: E) -> GenericError {
+    format!("{}: {}", p.display(), e).into()
+}
+
+/// Same as `path_error`, keeping the kind of `e` so permission errors can be
+/// told apart.
+fn io_path_error(p: &Path, e: io::Error) -> GenericError {
+    io::Error::new(e.kind(), format!("{}: {}", p.display(), e)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{memstorage::MemStorage, Storage};
+    use crate::test_util::write_file;
+
+    #[cfg(unix)]
+    #[test]
+    fn owner_and_group_are_read() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "a.txt", 1);
+        let metadata = fs::metadata(&path).unwrap();
+
+        for entry in [Entry::new(&path).unwrap(), Entry::new(tmp.path()).unwrap()] {
+            assert_eq!(entry.get_uid(), Some(metadata.uid()));
+            assert_eq!(entry.get_gid(), Some(metadata.gid()));
+            if metadata.uid() == 0 {
+                assert_eq!(entry.get_owner_name().as_deref(), Some("root"));
+            }
+        }
+    }
+
+    #[test]
+    fn display_path_styles() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "sub/a.txt", 1);
+        let root = format_path(tmp.path());
+        let entry = Entry::new(&path).unwrap();
+
+        assert_eq!(
+            entry.get_display_path(PathDisplay::Absolute, &root),
+            format_path(&path)
+        );
+        assert_eq!(
+            entry.get_display_path(PathDisplay::RelativeToRoot, &root),
+            format_path(Path::new("sub/a.txt"))
+        );
+        assert_eq!(
+            entry.get_display_path(PathDisplay::Basename, &root),
+            "a.txt"
+        );
+        assert_eq!(display_path(&root, PathDisplay::RelativeToRoot, &root), ".");
+    }
+
+    #[test]
+    fn verbatim_prefix_is_stripped() {
+        assert_eq!(strip_verbatim_prefix(r"\\?\C:\dir\a.txt"), r"C:\dir\a.txt");
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\UNC\server\share\a.txt"),
+            r"\\server\share\a.txt"
+        );
+        assert_eq!(strip_verbatim_prefix(r"C:\dir"), r"C:\dir");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn verbatim_paths_display_cleanly() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "a.txt", 1);
+        let verbatim = std::fs::canonicalize(&path).unwrap();
+        assert!(verbatim.to_string_lossy().starts_with(r"\\?\"));
+
+        let entry = Entry::new(&verbatim).unwrap();
+        assert_eq!(entry.get_name(), "a.txt");
+        assert!(!entry.get_format_path().starts_with(r"\\?\"));
+    }
+
+    #[test]
+    fn get_path_keeps_tricky_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "spa ce ünï #1.txt", 1);
+
+        let entry = Entry::new(&path).unwrap();
+        assert_eq!(entry.get_path(), path.as_path());
+        assert_eq!(entry.get_path().extension().unwrap(), "txt");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn get_path_keeps_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(OsStr::from_bytes(b"bad-\xff.bin"));
+        fs::write(&path, b"x").unwrap();
+
+        let entry = Entry::new(&path).unwrap();
+        assert_eq!(entry.get_path(), path.as_path());
+        assert_ne!(Path::new(&entry.get_format_path()), path.as_path());
+    }
+
+    #[test]
+    fn time_errors_explain_missing_times() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "a.txt", 1);
+        let metadata = fs::metadata(&path).unwrap();
+        let options = ScanOptions {
+            collect_times: false,
+            ..ScanOptions::default()
+        };
+        let entry = Entry::new_with_options(&path, None, 0, &options).unwrap();
+        assert!(entry.get_modified().is_none());
+        let errors = entry.get_time_errors();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[1].starts_with("modified: timestamps were not collected"));
+
+        if metadata.created().is_ok() {
+            let entry = Entry::new(&path).unwrap();
+            assert!(entry.get_time_errors().is_empty());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unsupported_creation_time_is_explained() {
+        // procfs has no birth time.
+        let entry = match Entry::new("/proc/version") {
+            Ok(entry) if entry.get_created().is_none() => entry,
+            _ => return,
+        };
+        let errors = entry.get_time_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("created: "));
+        assert!(errors[0].contains("not available") || errors[0].contains("Unsupported"));
+    }
+
+    #[test]
+    fn from_metadata_matches_stat() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = write_file(tmp.path(), "sub/a.txt", 12);
+        let parent: Arc<str> = Arc::from(format_path(&tmp.path().join("sub")));
+
+        let metadata = fs::metadata(&path).unwrap();
+        let entry = Entry::from_metadata(&path, Some(parent.clone()), 1, &metadata);
+        assert!(!entry.is_dir());
+        assert_eq!(entry.get_size(), 12);
+        assert_eq!(entry.get_name(), "a.txt");
+        assert_eq!(entry.get_parent().as_deref(), Some(&*parent));
+        assert_eq!(
+            entry.get_format_path(),
+            Entry::new(&path).unwrap().get_format_path()
+        );
+
+        let dir_metadata = fs::metadata(tmp.path().join("sub")).unwrap();
+        assert!(Entry::from_metadata(tmp.path().join("sub"), None, 0, &dir_metadata).is_dir());
+    }
+
+    #[test]
+    fn reserved_names_are_classified() {
+        for name in [
+            "CON",
+            "con",
+            "Nul.txt",
+            "aux.tar.gz",
+            "COM1",
+            "lpt9.log",
+            "a.",
+            "b ",
+        ] {
+            assert!(is_reserved_name(name), "{}", name);
+        }
+        for name in [
+            "console", "COM0", "COM10", "LPT", "null.txt", ".hidden", "a.b",
+        ] {
+            assert!(!is_reserved_name(name), "{}", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reserved_names_are_kept_and_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "CON.txt", 1);
+        write_file(tmp.path(), "fine.txt", 1);
+
+        let mut dir = match Entry::new(tmp.path()).unwrap() {
+            Entry::Dir(dir) => dir,
+            Entry::File(_) => unreachable!(),
+        };
+        let storage: MemStorage<String, Entry> = MemStorage::new();
+        let errors = dir.load_children_with_storage(&storage, &ScanOptions::default());
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("reserved on Windows"));
+        assert_eq!(storage.len(), 2);
+        let key = format_path(&tmp.path().join("CON.txt"));
+        assert!(storage.get(&key).unwrap().is_reserved_name());
+    }
+}
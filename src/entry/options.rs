@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::ContentKind;
+use crate::matcher::ExcludeSet;
+use crate::walk::{PauseFlag, ReadTimer, ScanProgress};
+use crate::GenericError;
+
+/// Most workers a scan spawns when the thread count is left to the cpu
+/// count. Scans are mostly waiting on the disk, so more rarely helps.
+pub const MAX_AUTO_THREADS: usize = 32;
+
+/// Name of the file `drfs` reads default options from.
+pub const CONFIG_FILE: &str = "drfs.toml";
+
+/// Tuning knobs for a scan, `ScanOptions::default()` scans everything using
+/// one worker per cpu.
+///
+/// Can be read from TOML with the field names as keys, missing keys keep
+/// their default, see `from_toml`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanOptions {
+    /// Number of worker threads, `None` means one per cpu up to
+    /// `MAX_AUTO_THREADS`. With a single thread the scan runs on the calling
+    /// thread.
+    pub threads: Option<usize>,
+    /// Read accessed/modified/created times. Turning it off saves three
+    /// metadata calls per entry and leaves the time getters returning `None`.
+    pub collect_times: bool,
+    /// After the scan, re-stat every n-th file and report the ones whose size
+    /// changed meanwhile, a sign the tree was modified while being scanned.
+    pub verify_sizes_every: Option<usize>,
+    /// Keep at most this many errors, later ones are only counted so a tree
+    /// full of unreadable entries can't exhaust memory.
+    pub max_errors: Option<usize>,
+    /// Run scan workers at lowered cpu and io priority. Best effort, see
+    /// `crate::priority`.
+    pub low_priority: bool,
+    /// Size block devices by their capacity rather than their metadata
+    /// length, which is 0. Needs the `device-size` feature and read access
+    /// to the device, otherwise the metadata length is kept.
+    pub device_sizes: bool,
+    /// Most tasks an idle worker takes at once from the shared queue or from
+    /// another worker. Large batches mean less contention but can leave other
+    /// workers idle on deep trees. `None` keeps crossbeam's default for the
+    /// shared queue and steals single tasks from other workers.
+    pub steal_batch: Option<usize>,
+    /// Stop reading new directories once the scanned files add up to more
+    /// than this many bytes. Entries already queued are still stored, so the
+    /// total can go somewhat over the budget.
+    pub max_bytes: Option<u64>,
+    /// Leave out files whose first bytes identify them as one of these
+    /// kinds, whatever their extension. Every file gets opened and read, so
+    /// scans get noticeably slower. Needs the `content-type` feature,
+    /// otherwise nothing is skipped.
+    pub skip_content: Vec<ContentKind>,
+    /// Flag directories on another device than their parent as mount
+    /// points, see `DirEntry::is_mount_point`.
+    pub detect_mounts: bool,
+    /// Leave entries shallower than this out of `find`, `largest_files`,
+    /// `largest_dirs`, `deepest_dirs` and `flat_listing`, the root being at
+    /// depth 0. They are still scanned and still count toward sizes.
+    pub min_depth: Option<usize>,
+    /// Don't read directories this deep or deeper, the root being at depth
+    /// 0, so entries below it are not stored. `Some(0)` leaves only the root.
+    pub max_depth: Option<usize>,
+    /// Leave out entries matching any of these patterns, and for directories
+    /// everything below them. The root is always scanned.
+    pub exclude: ExcludeSet,
+    /// Measure how long each directory takes to read, see
+    /// `EntryWrapper::slowest_dirs`.
+    pub time_reads: bool,
+    /// Turns the measured read times into the recorded ones when
+    /// `time_reads` is set, the default keeps them. Never read from TOML.
+    #[serde(skip)]
+    pub read_timer: ReadTimer,
+    /// Once more than this fraction of the scanned directories couldn't be
+    /// read for lack of permissions, `load_entry` adds a
+    /// `DrfsError::InsufficientPermissions` to its errors. `None` never does.
+    pub permission_error_ratio: Option<f64>,
+    /// Descend into symlinked directories and size symlinked files by their
+    /// target. Otherwise symlinks are stored as entries of their own, see
+    /// `Entry::is_symlink`. Directories reached twice are only read once.
+    pub follow_symlinks: bool,
+    /// Lets another thread pause the scan and resume it, keep a clone of it
+    /// before starting. Never read from TOML.
+    #[serde(skip)]
+    pub pause: PauseFlag,
+    /// Counts the entries processed and still queued while scanning, keep a
+    /// clone of it to report progress. Never read from TOML.
+    #[serde(skip)]
+    pub progress: ScanProgress,
+}
+
+impl ScanOptions {
+    /// Options from a TOML document such as `threads = 4`. Unknown keys are
+    /// an error so typos don't go unnoticed.
+    pub fn from_toml(text: &str) -> Result<ScanOptions, GenericError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Options from the TOML file at `path`, see `from_toml`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ScanOptions, GenericError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        ScanOptions::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Worker threads a scan with these options uses, always at least one.
+    pub fn thread_count(&self) -> usize {
+        self.thread_count_with_cpus(num_cpus::get())
+    }
+
+    /// Same as `thread_count` on a machine with `cpus` cpus.
+    pub(crate) fn thread_count_with_cpus(&self, cpus: usize) -> usize {
+        match self.threads {
+            Some(threads) => threads.max(1),
+            None => cpus.clamp(1, MAX_AUTO_THREADS),
+        }
+    }
+
+    /// Whether an entry at `depth` is deep enough to be reported.
+    pub fn reports_depth(&self, depth: usize) -> bool {
+        self.min_depth.is_none_or(|min| depth >= min)
+    }
+
+    /// Whether a directory at `depth` gets its children read.
+    pub fn descends_into(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max| depth < max)
+    }
+
+    /// Steal batch limit with a zero limit raised to one.
+    pub fn steal_batch_limit(&self) -> Option<usize> {
+        self.steal_batch.map(|limit| limit.max(1))
+    }
+}
+
+impl Default for ScanOptions {
+    fn default() -> ScanOptions {
+        ScanOptions {
+            threads: None,
+            collect_times: true,
+            verify_sizes_every: None,
+            max_errors: None,
+            low_priority: false,
+            device_sizes: false,
+            steal_batch: None,
+            max_bytes: None,
+            skip_content: vec![],
+            detect_mounts: false,
+            min_depth: None,
+            max_depth: None,
+            exclude: ExcludeSet::default(),
+            time_reads: false,
+            read_timer: ReadTimer::default(),
+            permission_error_ratio: Some(0.5),
+            follow_symlinks: false,
+            pause: PauseFlag::default(),
+            progress: ScanProgress::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_count_is_clamped_for_odd_cpu_counts() {
+        let auto = ScanOptions::default();
+        assert_eq!(auto.thread_count_with_cpus(0), 1);
+        assert_eq!(auto.thread_count_with_cpus(1), 1);
+        assert_eq!(auto.thread_count_with_cpus(8), 8);
+        assert_eq!(auto.thread_count_with_cpus(512), MAX_AUTO_THREADS);
+
+        let explicit = ScanOptions {
+            threads: Some(100),
+            ..ScanOptions::default()
+        };
+        assert_eq!(explicit.thread_count_with_cpus(1), 100);
+    }
+
+    #[test]
+    fn options_are_read_from_toml() {
+        let options = ScanOptions::from_toml(
+            "threads = 4\n\
+             collect_times = false\n\
+             max_errors = 100\n\
+             skip_content = [\"image\", \"video\"]\n\
+             exclude = [\"*.bak\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            options,
+            ScanOptions {
+                threads: Some(4),
+                collect_times: false,
+                max_errors: Some(100),
+                skip_content: vec![ContentKind::Image, ContentKind::Video],
+                exclude: ExcludeSet::new(["*.bak"]).unwrap(),
+                ..ScanOptions::default()
+            }
+        );
+
+        assert_eq!(ScanOptions::from_toml("").unwrap(), ScanOptions::default());
+        assert!(ScanOptions::from_toml("thread = 4").is_err());
+        assert!(ScanOptions::from_toml("skip_content = [\"pictures\"]").is_err());
+        assert!(ScanOptions::from_toml("exclude = [\"[\"]").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! Names of Unix users and groups, read once from `/etc/passwd` and
+//! `/etc/group`. Accounts only known to NSS services such as LDAP aren't
+//! found.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+pub(crate) fn user_name(uid: u32) -> Option<String> {
+    static USERS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    USERS
+        .get_or_init(|| read_ids("/etc/passwd"))
+        .get(&uid)
+        .cloned()
+}
+
+pub(crate) fn group_name(gid: u32) -> Option<String> {
+    static GROUPS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+    GROUPS
+        .get_or_init(|| read_ids("/etc/group"))
+        .get(&gid)
+        .cloned()
+}
+
+fn read_ids(path: &str) -> HashMap<u32, String> {
+    fs::read_to_string(path)
+        .map(|text| parse_ids(&text))
+        .unwrap_or_default()
+}
+
+/// Ids from `name:password:id:...` lines, the layout both files share.
+/// Malformed lines are skipped, the first name for an id wins.
+fn parse_ids(text: &str) -> HashMap<u32, String> {
+    let mut ids = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.split(':');
+        let (name, id) = match (fields.next(), fields.nth(1)) {
+            (Some(name), Some(id)) => (name, id),
+            _ => continue,
+        };
+        if let Ok(id) = id.parse() {
+            ids.entry(id).or_insert_with(|| name.to_string());
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_parsed_from_passwd_lines() {
+        let ids = parse_ids(
+            "root:x:0:0:root:/root:/bin/bash\n\
+             # comment\n\
+             toor:x:0:0::/root:/bin/sh\n\
+             daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+             broken:x\n\
+             staff:x:50:alice,bob\n",
+        );
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[&0], "root");
+        assert_eq!(ids[&1], "daemon");
+        assert_eq!(ids[&50], "staff");
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Result of an approximate scan that only read part of the tree, see
+/// `EntryWrapper::estimate_size`. Never mix these numbers with exact ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApproxResult {
+    /// Estimated total size of the files in bytes.
+    pub estimate: u64,
+    /// Estimated number of entries.
+    pub entries: u64,
+    /// Share of the estimated entries that were actually read, between 0 and
+    /// 1. The closer to 1, the more the estimate can be trusted.
+    pub sampled_fraction: f64,
+}
+
+/// Whether the directory at `path` is descended into when sampling with
+/// `fraction`. Decided by hashing the path, so the same tree is always
+/// sampled the same way.
+pub(crate) fn is_sampled(path: &str, fraction: f64) -> bool {
+    if fraction >= 1.0 {
+        return true;
+    }
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    (hasher.finish() as f64 / u64::MAX as f64) < fraction
+}
@@ -0,0 +1,51 @@
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::GenericError;
+
+/// Aggregates of one directory in a `DirSkeleton`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirSummary {
+    pub path: String,
+    pub parent: Option<String>,
+    pub depth: usize,
+    /// Recursive size of the files below.
+    pub size: u64,
+    /// Recursive number of entries below, files and directories.
+    pub entries: u64,
+}
+
+/// Directories of a scan with their aggregates but without any file, written
+/// by `EntryWrapper::to_json_dirs_only`. Small and quick to reload for
+/// navigating a tree without rescanning it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirSkeleton {
+    pub root: String,
+    /// Sorted by path.
+    pub dirs: Vec<DirSummary>,
+}
+
+impl DirSkeleton {
+    /// Reads a skeleton written by `EntryWrapper::to_json_dirs_only`.
+    pub fn from_json<R: Read>(r: R) -> Result<DirSkeleton, GenericError> {
+        let mut skeleton: DirSkeleton = serde_json::from_reader(r)?;
+        skeleton.dirs.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(skeleton)
+    }
+
+    pub fn get(&self, path: &str) -> Option<&DirSummary> {
+        self.dirs
+            .binary_search_by(|dir| dir.path.as_str().cmp(path))
+            .ok()
+            .map(|i| &self.dirs[i])
+    }
+
+    /// Direct subdirectories of `path`.
+    pub fn children(&self, path: &str) -> Vec<&DirSummary> {
+        self.dirs
+            .iter()
+            .filter(|dir| dir.parent.as_deref() == Some(path))
+            .collect()
+    }
+}
@@ -0,0 +1,47 @@
+//! Serde helpers writing entry timestamps as RFC 3339 strings in UTC, with
+//! full precision so they read back unchanged.
+
+use std::io;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+use super::EntryTime;
+
+pub(crate) fn serialize<S: Serializer>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+    let datetime: DateTime<Utc> = (*time).into();
+    s.serialize_str(&datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<SystemTime, D::Error> {
+    let text = String::deserialize(d)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(SystemTime::from)
+        .map_err(de::Error::custom)
+}
+
+/// Times that couldn't be read are written as `null` and read back as an
+/// error saying so, the original error is lost.
+pub(crate) mod result {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(time: &EntryTime, s: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Ok(time) => super::serialize(time, s),
+            Err(_) => s.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<EntryTime, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(text) => DateTime::parse_from_rfc3339(&text)
+                .map(|time| Ok(SystemTime::from(time)))
+                .map_err(de::Error::custom),
+            None => Ok(Err(Arc::new(io::Error::other(
+                "time was not available when the entry was saved",
+            )))),
+        }
+    }
+}
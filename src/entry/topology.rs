@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::Entry;
+use crate::store::Storage;
+use crate::GenericError;
+
+/// Parent/children adjacency of the entries in a storage, without the
+/// entries themselves. Kept next to a storage with large entry blobs, it lets
+/// a tree be navigated without reading any entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyIndex {
+    /// Storage keys of the direct children of every directory, in the order
+    /// of `DirEntry::get_children`.
+    children: HashMap<String, Vec<String>>,
+    /// Storage key of the parent of every entry that has one.
+    parents: HashMap<String, String>,
+}
+
+impl TopologyIndex {
+    /// Records the adjacency of every entry currently in `storage`.
+    pub fn from_storage(storage: &dyn Storage<String, Entry>) -> TopologyIndex {
+        let mut topology = TopologyIndex::default();
+        for entry in storage.values() {
+            let path = entry.get_format_path();
+            if let Some(parent) = entry.get_parent() {
+                topology.parents.insert(path.clone(), parent);
+            }
+            if let Entry::Dir(dir) = entry {
+                topology.children.insert(path, dir.get_children().to_vec());
+            }
+        }
+        topology
+    }
+
+    /// Reads an index written by `to_json`.
+    pub fn from_json<R: Read>(r: R) -> Result<TopologyIndex, GenericError> {
+        Ok(serde_json::from_reader(r)?)
+    }
+
+    pub fn to_json<W: Write>(&self, w: W) -> Result<(), GenericError> {
+        serde_json::to_writer(w, self)?;
+        Ok(())
+    }
+
+    /// Direct children of the directory `path`, `None` if it isn't a
+    /// recorded directory.
+    pub fn children_of(&self, path: &str) -> Option<&[String]> {
+        self.children.get(path).map(Vec::as_slice)
+    }
+
+    /// Parent of `path`, `None` for the root and for unknown paths.
+    pub fn parent_of(&self, path: &str) -> Option<&str> {
+        self.parents.get(path).map(String::as_str)
+    }
+}
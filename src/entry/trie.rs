@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+/// Index over stored paths keyed by path component, so everything below a
+/// prefix is found without looking at unrelated subtrees.
+#[derive(Debug, Default)]
+pub struct PathTrie {
+    root: Node,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    /// Storage key of the entry ending at this node, if any.
+    key: Option<String>,
+    children: HashMap<String, Node>,
+}
+
+impl PathTrie {
+    pub fn new() -> PathTrie {
+        PathTrie::default()
+    }
+
+    pub fn insert(&mut self, key: &str) {
+        let mut node = &mut self.root;
+        for component in components(key) {
+            node = node.children.entry(component).or_default();
+        }
+        node.key = Some(key.to_string());
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        remove(&mut self.root, &components(key));
+    }
+
+    /// Keys strictly below `prefix`, sorted.
+    pub fn entries_under(&self, prefix: &str) -> Vec<String> {
+        let mut node = &self.root;
+        for component in components(prefix) {
+            match node.children.get(&component) {
+                Some(child) => node = child,
+                None => return vec![],
+            }
+        }
+
+        let mut keys = vec![];
+        let mut stack: Vec<&Node> = node.children.values().collect();
+        while let Some(node) = stack.pop() {
+            if let Some(key) = &node.key {
+                keys.push(key.clone());
+            }
+            stack.extend(node.children.values());
+        }
+        keys.sort();
+        keys
+    }
+}
+
+/// Removes the key at `path` below `node`, pruning nodes left empty.
+/// Returns whether `node` itself became empty.
+fn remove(node: &mut Node, path: &[String]) -> bool {
+    match path.split_first() {
+        None => node.key = None,
+        Some((first, rest)) => {
+            if let Some(child) = node.children.get_mut(first) {
+                if remove(child, rest) {
+                    node.children.remove(first);
+                }
+            }
+        }
+    }
+    node.key.is_none() && node.children.is_empty()
+}
+
+fn components(key: &str) -> Vec<String> {
+    Path::new(key)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_remove_and_query() {
+        let mut trie = PathTrie::new();
+        for key in &["/a", "/a/b", "/a/b/c", "/a/bc", "/ab/x"] {
+            trie.insert(key);
+        }
+
+        assert_eq!(trie.entries_under("/a"), vec!["/a/b", "/a/b/c", "/a/bc"]);
+        assert_eq!(trie.entries_under("/a/b"), vec!["/a/b/c"]);
+        assert!(trie.entries_under("/missing").is_empty());
+
+        trie.remove("/a/b/c");
+        assert!(trie.entries_under("/a/b").is_empty());
+        assert_eq!(trie.entries_under("/a"), vec!["/a/b", "/a/bc"]);
+    }
+}
@@ -0,0 +1,3739 @@
+use std::cmp::{self, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    display_path, format_path, ApproxResult, DirSkeleton, DirSummary, Entry, ErrorCollector,
+    FileCategory, FileEntry, PathDisplay, PathTrie, ScanOptions, TopologyIndex, WalkEvent,
+};
+use crate::error::DrfsError;
+use crate::matcher::Matcher;
+#[cfg(feature = "compressed-storage")]
+use crate::store::compressedmemstorage::CompressedMemStorage;
+use crate::store::memstorage::MemStorage;
+#[cfg(feature = "sqlite")]
+use crate::store::sqlitestorage::SqliteStorage;
+use crate::store::Storage;
+use crate::util::{format_size, SortOrder};
+use crate::walk::{run_parallel_with_state, walk_parallel};
+use crate::GenericError;
+
+/// File size totals computed by `EntryWrapper::size_overview`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SizeOverview {
+    /// Sum of logical file sizes.
+    pub apparent: u64,
+    /// Bytes allocated on disk, hardlinked files counted once.
+    pub allocated: u64,
+    /// Sum of logical file sizes with hardlinked files counted once.
+    pub dedup_apparent: u64,
+}
+
+/// Summary of a scan built by `EntryWrapper::build_report`, meant to be
+/// serialized by the caller in whatever format they need.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanReport {
+    pub root: String,
+    /// Entries below the root.
+    pub entries: usize,
+    pub files: usize,
+    pub dirs: usize,
+    pub sizes: SizeOverview,
+    /// Largest files as `(path, size)`, at most `ScanReport::TOP_FILES`.
+    pub top_files: Vec<(String, u64)>,
+    /// Total size and file count by lowercased extension.
+    pub extensions: BTreeMap<String, (u64, usize)>,
+    /// Errors returned by the last `load_entry`.
+    pub errors: usize,
+    /// Errors the last `load_entry` dropped because of `max_errors`.
+    pub errors_truncated: usize,
+    /// Entries with at least one time that couldn't be read.
+    pub time_errors: usize,
+    /// Whether the last `load_entry` stopped early because of `max_bytes`.
+    pub truncated: bool,
+}
+
+impl ScanReport {
+    /// Number of files `build_report` lists in `top_files`.
+    pub const TOP_FILES: usize = 10;
+}
+
+/// A direct child of the root as listed by `EntryWrapper::get_children_detailed`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildInfo {
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    /// Recursive size for directories.
+    pub size: u64,
+}
+
+/// What `EntryWrapper::get_children_detailed` sorts by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildKey {
+    /// Names in the given order.
+    Name(SortOrder),
+    /// Largest first, ties by path.
+    Size,
+}
+
+/// Paths with risky permission bits found by `EntryWrapper::permission_audit`,
+/// each list sorted. A path can be in several of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PermissionAudit {
+    pub world_writable: Vec<String>,
+    pub setuid: Vec<String>,
+    pub setgid: Vec<String>,
+}
+
+/// File paths split by size for a quick triage, each tier sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeTiers {
+    pub big: Vec<String>,
+    pub medium: Vec<String>,
+    pub small: Vec<String>,
+}
+
+impl SizeTiers {
+    /// Smallest size `size_tiers` puts in the medium tier, 1 MiB.
+    pub const MEDIUM: u64 = 1024 * 1024;
+    /// Smallest size `size_tiers` puts in the big tier, 100 MiB.
+    pub const BIG: u64 = 100 * 1024 * 1024;
+}
+
+/// A file whose size differs between the scan and a later re-stat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeChange {
+    pub path: String,
+    pub scanned: u64,
+    pub current: u64,
+}
+
+impl fmt::Display for SizeChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: size changed during scan ({} -> {} bytes)",
+            self.path, self.scanned, self.current
+        )
+    }
+}
+
+/// Difference between two scans of the same tree, see `EntryWrapper::diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeChange {
+    Added(String),
+    Removed(String),
+    /// A file that vanished from `from` while the same file, by
+    /// `(device, inode)`, showed up at `to`.
+    Moved {
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for TreeChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeChange::Added(path) => write!(f, "+ {}", path),
+            TreeChange::Removed(path) => write!(f, "- {}", path),
+            TreeChange::Moved { from, to } => write!(f, "{} -> {}", from, to),
+        }
+    }
+}
+
+/// A scan as written by `EntryWrapper::save` and `to_json`.
+#[derive(Serialize, Deserialize)]
+struct SavedScan {
+    root: String,
+    entries: BTreeMap<String, Entry>,
+}
+
+/// Owns the root of a scan and the storage holding every entry below it.
+pub struct EntryWrapper {
+    root: String,
+    storage: Arc<dyn Storage<String, Entry>>,
+    /// Directories whose direct children are in storage, see `ensure_loaded`.
+    loaded_dirs: Mutex<HashSet<String>>,
+    fully_loaded: AtomicBool,
+    options: ScanOptions,
+    /// Worker threads spawned by the last `load_entry`, 0 before the first one.
+    last_thread_count: AtomicUsize,
+    /// Errors returned by the last `load_entry`.
+    errors_reported: AtomicUsize,
+    /// Errors dropped by the last `load_entry` because of `max_errors`.
+    errors_truncated: AtomicUsize,
+    /// Whether the last `load_entry` skipped directories because of `max_bytes`.
+    bytes_truncated: AtomicBool,
+    /// Prefix index over storage keys, only kept once `build_index` was called.
+    index: RwLock<Option<PathTrie>>,
+    /// Largest file below each stored directory, computed on first use and
+    /// dropped whenever storage changes.
+    max_file_sizes: RwLock<Option<HashMap<String, u64>>>,
+    /// `structure_hash` of storage, kept like `max_file_sizes`.
+    structure_hash: RwLock<Option<u64>>,
+    /// Refuse every operation that drops stored entries, see `set_read_only`.
+    read_only: bool,
+}
+
+impl EntryWrapper {
+    pub fn new_with_memstorage<P: AsRef<Path>>(path: P) -> Result<EntryWrapper, GenericError> {
+        EntryWrapper::new_with_storage(path, Arc::new(MemStorage::new()))
+    }
+
+    /// Like `new_with_memstorage` but keeps entries compressed, see
+    /// `CompressedMemStorage`.
+    #[cfg(feature = "compressed-storage")]
+    pub fn new_with_compressed_storage<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<EntryWrapper, GenericError> {
+        EntryWrapper::new_with_storage(path, Arc::new(CompressedMemStorage::new()))
+    }
+
+    /// Like `new_with_memstorage` but keeps entries in the SQLite database at
+    /// `db_path`. Entries left there by an earlier scan are removed first.
+    #[cfg(feature = "sqlite")]
+    pub fn new_with_sqlite<P: AsRef<Path>, D: AsRef<Path>>(
+        path: P,
+        db_path: D,
+    ) -> Result<EntryWrapper, GenericError> {
+        EntryWrapper::new_with_storage(path, Arc::new(SqliteStorage::create(db_path)?))
+    }
+
+    /// Creates the root entry for `path` and stores it, children are not loaded yet.
+    pub fn new_with_storage<P: AsRef<Path>>(
+        path: P,
+        storage: Arc<dyn Storage<String, Entry>>,
+    ) -> Result<EntryWrapper, GenericError> {
+        let path = fs::canonicalize(path.as_ref())
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        let entry = Entry::new(&path)?;
+        let root = entry.get_format_path();
+        storage.set(root.clone(), entry);
+
+        Ok(EntryWrapper::with_storage(root, storage, false))
+    }
+
+    /// Reads back a scan written by `save` into a new `MemStorage`, without
+    /// touching the scanned files. The result counts as fully loaded.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EntryWrapper, GenericError> {
+        let path = path.as_ref();
+        let file = fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let saved: SavedScan = serde_json::from_reader(io::BufReader::new(file))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !saved.entries.contains_key(&saved.root) {
+            return Err(format!("{}: root entry is missing", path.display()).into());
+        }
+
+        let storage = MemStorage::new();
+        for (key, entry) in saved.entries {
+            storage.set(key, entry);
+        }
+        Ok(EntryWrapper::with_storage(
+            saved.root,
+            Arc::new(storage),
+            true,
+        ))
+    }
+
+    fn with_storage(
+        root: String,
+        storage: Arc<dyn Storage<String, Entry>>,
+        fully_loaded: bool,
+    ) -> EntryWrapper {
+        EntryWrapper {
+            root,
+            storage,
+            loaded_dirs: Mutex::new(HashSet::new()),
+            fully_loaded: AtomicBool::new(fully_loaded),
+            options: ScanOptions::default(),
+            last_thread_count: AtomicUsize::new(0),
+            errors_reported: AtomicUsize::new(0),
+            errors_truncated: AtomicUsize::new(0),
+            bytes_truncated: AtomicBool::new(false),
+            index: RwLock::new(None),
+            max_file_sizes: RwLock::new(None),
+            structure_hash: RwLock::new(None),
+            read_only: false,
+        }
+    }
+
+    /// Walks `root` calling `visitor` once per entry, the root included,
+    /// without building any storage. Returns the errors hit while walking.
+    ///
+    /// `visitor` is called concurrently from the scan workers.
+    pub fn visit<P, F>(root: P, visitor: F) -> Result<Vec<GenericError>, GenericError>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Entry) + Sync,
+    {
+        let mut entry = Entry::new(root)?;
+        visitor(&entry);
+        match entry {
+            Entry::Dir(ref mut dir) => Ok(dir.visit_all_children(&ScanOptions::default(), visitor)),
+            Entry::File(_) => Ok(vec![]),
+        }
+    }
+
+    /// Walks `root` on a background thread, streaming a `WalkEvent` per
+    /// directory listed, file found and error hit, e.g. to feed an external
+    /// index. Nothing is stored. The channel closes once the walk is done.
+    pub fn walk_events<P: AsRef<Path>>(root: P) -> Receiver<WalkEvent> {
+        let root = root.as_ref().to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // Sends only fail once the receiver is gone, the walk is wasted then.
+            let send = |event| {
+                let _ = sender.send(event);
+            };
+            match Entry::new(&root) {
+                Ok(Entry::Dir(mut dir)) => dir.walk_all_children(&ScanOptions::default(), send),
+                Ok(Entry::File(file)) => send(WalkEvent::FoundFile {
+                    path: file.get_format_path(),
+                    depth: file.get_depth(),
+                    size: file.get_size(),
+                    modified: file.get_modified(),
+                }),
+                Err(e) => send(WalkEvent::Error(e)),
+            }
+        });
+        receiver
+    }
+
+    /// Drops roots that resolve to the same directory as an earlier one, e.g.
+    /// through a symlink, so scanning each root doesn't count it twice.
+    /// Returns the roots to scan in their original order and the dropped
+    /// ones as `(dropped, same_as)`. Roots that can't be read are kept, so
+    /// scanning them reports the error.
+    pub fn dedup_roots<P: AsRef<Path>>(roots: &[P]) -> (Vec<PathBuf>, Vec<(PathBuf, PathBuf)>) {
+        let mut seen: HashMap<RootId, PathBuf> = HashMap::new();
+        let mut kept = vec![];
+        let mut dropped = vec![];
+        for root in roots {
+            let root = root.as_ref().to_path_buf();
+            match root_id(&root) {
+                Some(id) => match seen.get(&id) {
+                    Some(first) => dropped.push((root, first.clone())),
+                    None => {
+                        seen.insert(id, root.clone());
+                        kept.push(root);
+                    }
+                },
+                None => kept.push(root),
+            }
+        }
+        (kept, dropped)
+    }
+
+    /// Approximates the size of the files below `root` by reading only about
+    /// `fraction` of its subdirectories, see `DirEntry::sample_all_children`.
+    /// Nothing is stored. Use `load_entry` when exact numbers are needed.
+    pub fn estimate_size<P: AsRef<Path>>(
+        root: P,
+        fraction: f64,
+    ) -> Result<(ApproxResult, Vec<GenericError>), GenericError> {
+        match Entry::new(root)? {
+            Entry::Dir(mut dir) => Ok(dir.sample_all_children(&ScanOptions::default(), fraction)),
+            Entry::File(file) => Ok((
+                ApproxResult {
+                    estimate: file.get_size(),
+                    entries: 0,
+                    sampled_fraction: 1.0,
+                },
+                vec![],
+            )),
+        }
+    }
+
+    /// Whether the files below `root` add up to more than `threshold` bytes.
+    /// The walk stops reading directories as soon as the answer is known,
+    /// so a yes on a big tree is much cheaper than a full scan. A root that
+    /// can't be read counts as empty.
+    pub fn size_exceeds<P: AsRef<Path>>(root: P, threshold: u64) -> bool {
+        size_exceeds_with(root, threshold, &ScanOptions::default(), |_| {})
+    }
+
+    /// Walks `root` only to report the entries that couldn't be read, nothing
+    /// is kept. A root that can't be read is reported as the only error.
+    pub fn collect_errors_only<P: AsRef<Path>>(root: P) -> Vec<GenericError> {
+        match Entry::new(root) {
+            Ok(Entry::Dir(mut dir)) => {
+                dir.load_all_children_with_storage(None, &ScanOptions::default())
+            }
+            Ok(Entry::File(_)) => vec![],
+            Err(e) => vec![e],
+        }
+    }
+
+    /// The `n` largest files below `root`, largest first, like `largest_files`
+    /// but without storing anything: each worker offers its files to a shared
+    /// heap that never holds more than `n`. Entries that can't be read are
+    /// left out.
+    pub fn stream_largest_files<P: AsRef<Path>>(root: P, n: usize) -> Vec<(String, u64)> {
+        if n == 0 {
+            return vec![];
+        }
+
+        // Min-heap on size, ties broken so the path sorting last goes first.
+        let heap = Mutex::new(BinaryHeap::with_capacity(n + 1));
+        walk_parallel(root, &ScanOptions::default(), |p, metadata| {
+            if metadata.is_dir() {
+                return;
+            }
+            let mut heap = heap.lock().unwrap();
+            heap.push(Reverse((metadata.len(), Reverse(format_path(p)))));
+            if heap.len() > n {
+                heap.pop();
+            }
+        });
+
+        let files = heap
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|Reverse((size, Reverse(path)))| (path, size))
+            .collect();
+        top_by_size(files, None)
+    }
+
+    /// Recursively loads everything below the root into storage.
+    pub fn load_entry(&self) -> Vec<GenericError> {
+        self.load_entry_with(&self.options)
+    }
+
+    /// Same as `load_entry`, with the scan workers running at lowered cpu
+    /// and io priority where the platform supports it.
+    pub fn scan_low_priority(&self) -> Vec<GenericError> {
+        let options = ScanOptions {
+            low_priority: true,
+            ..self.options.clone()
+        };
+        self.load_entry_with(&options)
+    }
+
+    fn load_entry_with(&self, options: &ScanOptions) -> Vec<GenericError> {
+        let mut entry = match self.storage.get(&self.root) {
+            Some(entry) => entry,
+            None => return vec![format!("{}: root entry is missing", self.root).into()],
+        };
+
+        let collector = ErrorCollector::new(options.max_errors);
+        let mut bytes_truncated = false;
+        if let Entry::Dir(ref mut dir) = entry {
+            self.last_thread_count
+                .store(options.thread_count(), Ordering::SeqCst);
+            bytes_truncated =
+                dir.load_all_children_with_error_sink(Some(self.storage.clone()), options, &|e| {
+                    collector.push(e)
+                });
+        }
+        let denied = collector.permission_denied();
+        let (mut errors, truncated) = collector.into_parts();
+        self.errors_truncated.store(truncated, Ordering::SeqCst);
+        self.bytes_truncated
+            .store(bytes_truncated, Ordering::SeqCst);
+        self.storage.set(self.root.clone(), entry);
+        self.fully_loaded.store(true, Ordering::SeqCst);
+        self.refresh_index();
+        self.drop_caches();
+
+        if let Some(max_ratio) = options.permission_error_ratio {
+            let dirs = self
+                .storage
+                .values()
+                .iter()
+                .filter(|entry| entry.is_dir())
+                .count();
+            let error_ratio = denied as f64 / dirs.max(1) as f64;
+            if denied > 0 && error_ratio > max_ratio {
+                errors.push(Box::new(DrfsError::InsufficientPermissions { error_ratio }));
+            }
+        }
+
+        if let Some(every) = options.verify_sizes_every {
+            errors.extend(
+                self.find_size_changes(every)
+                    .into_iter()
+                    .map(|change| change.to_string().into()),
+            );
+        }
+
+        self.errors_reported.store(errors.len(), Ordering::SeqCst);
+        errors
+    }
+
+    /// Re-stats every `every`-th stored file (in path order) and reports the
+    /// ones whose size no longer matches what the scan recorded.
+    ///
+    /// Files are re-read the way the scan read them, so symlinks are compared
+    /// by their own size and block devices by their capacity if the scan
+    /// used `ScanOptions::device_sizes`.
+    pub fn find_size_changes(&self, every: usize) -> Vec<SizeChange> {
+        self.find_size_changes_by(every, |file| file.current_size(&self.options))
+    }
+
+    /// Same as `find_size_changes`, getting current sizes from `stat`.
+    pub fn find_size_changes_with<F>(&self, every: usize, stat: F) -> Vec<SizeChange>
+    where
+        F: Fn(&Path) -> io::Result<u64>,
+    {
+        self.find_size_changes_by(every, |file| stat(file.get_path()))
+    }
+
+    fn find_size_changes_by<F>(&self, every: usize, stat: F) -> Vec<SizeChange>
+    where
+        F: Fn(&FileEntry) -> io::Result<u64>,
+    {
+        let mut files: Vec<(String, FileEntry)> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) => Some((file.get_format_path(), file)),
+                Entry::Dir(_) => None,
+            })
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        files
+            .into_iter()
+            .step_by(every.max(1))
+            .filter_map(|(path, file)| match stat(&file) {
+                Ok(current) if current != file.get_size() => Some(SizeChange {
+                    path,
+                    scanned: file.get_size(),
+                    current,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Errors the last `load_entry` dropped instead of returning, see
+    /// `ScanOptions::max_errors`.
+    pub fn errors_truncated(&self) -> usize {
+        self.errors_truncated.load(Ordering::SeqCst)
+    }
+
+    /// Whether the last `load_entry` stopped reading directories after going
+    /// over `ScanOptions::max_bytes`, leaving storage with partial results.
+    pub fn is_truncated(&self) -> bool {
+        self.bytes_truncated.load(Ordering::SeqCst)
+    }
+
+    pub fn get_options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Makes operations that drop stored entries, like `retain_subtree`,
+    /// fail instead of acting. Scans still add and refresh entries.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Options used by later scans, entries already in storage are kept.
+    pub fn set_options(&mut self, options: ScanOptions) {
+        self.options = options;
+    }
+
+    /// Worker threads spawned by the last full scan, `None` if there was none.
+    pub fn last_scan_thread_count(&self) -> Option<usize> {
+        match self.last_thread_count.load(Ordering::SeqCst) {
+            0 => None,
+            count => Some(count),
+        }
+    }
+
+    /// Loads the direct children of the directory `path` unless that already
+    /// happened, either through an earlier call or a full `load_entry`.
+    ///
+    /// Returns whether the directory was read along with any errors hit doing so.
+    pub fn ensure_loaded(&self, path: &str) -> (bool, Vec<GenericError>) {
+        if self.is_loaded(path) {
+            return (false, vec![]);
+        }
+
+        let mut dir = match self.get_entry(path) {
+            Some(Entry::Dir(dir)) => dir,
+            Some(Entry::File(_)) => return (false, vec![]),
+            None => return (false, vec![format!("{}: not in storage", path).into()]),
+        };
+        let errors = dir.load_children_with_storage(self.storage.as_ref(), &self.options);
+        if let Some(index) = self.index.write().unwrap().as_mut() {
+            for child in dir.get_children() {
+                index.insert(child);
+            }
+        }
+        self.storage.set(path.to_string(), Entry::Dir(dir));
+        self.loaded_dirs.lock().unwrap().insert(path.to_string());
+        self.drop_caches();
+
+        (true, errors)
+    }
+
+    /// Whether the direct children of `path` are already in storage.
+    pub fn is_loaded(&self, path: &str) -> bool {
+        self.fully_loaded.load(Ordering::SeqCst) || self.loaded_dirs.lock().unwrap().contains(path)
+    }
+
+    /// Drops every stored entry outside of `path` and makes `path` the new root.
+    pub fn retain_subtree(&mut self, path: &str) -> Result<(), GenericError> {
+        if self.read_only {
+            return Err(format!("{}: storage is read-only", path).into());
+        }
+        match self.get_entry(path) {
+            Some(Entry::Dir(_)) => {}
+            Some(Entry::File(_)) => return Err(format!("{}: not a directory", path).into()),
+            None => return Err(format!("{}: not in storage", path).into()),
+        }
+
+        let subtree = Path::new(path);
+        let mut index = self.index.write().unwrap();
+        for key in self.storage.keys() {
+            if !Path::new(&key).starts_with(subtree) {
+                self.storage.remove(&key);
+                if let Some(index) = index.as_mut() {
+                    index.remove(&key);
+                }
+            }
+        }
+        drop(index);
+        self.loaded_dirs
+            .lock()
+            .unwrap()
+            .retain(|dir| Path::new(dir).starts_with(subtree));
+        self.drop_caches();
+        self.root = path.to_string();
+
+        Ok(())
+    }
+
+    /// Builds a prefix index over every stored path, which `entries_under`
+    /// uses from then on. The index is kept up to date by later scans.
+    pub fn build_index(&self) {
+        let mut index = PathTrie::new();
+        for key in self.storage.keys() {
+            index.insert(&key);
+        }
+        *self.index.write().unwrap() = Some(index);
+    }
+
+    /// Rebuilds the index after storage changed wholesale, if there is one.
+    fn refresh_index(&self) {
+        if self.index.read().unwrap().is_some() {
+            self.build_index();
+        }
+    }
+
+    /// Stored paths strictly below `prefix`, sorted. Uses the prefix index if
+    /// one was built, otherwise scans all stored paths.
+    pub fn entries_under(&self, prefix: &str) -> Vec<String> {
+        if let Some(index) = self.index.read().unwrap().as_ref() {
+            return index.entries_under(prefix);
+        }
+
+        let prefix = Path::new(prefix);
+        let mut keys: Vec<String> = self
+            .storage
+            .keys()
+            .into_iter()
+            .filter(|key| {
+                let path = Path::new(key);
+                path != prefix && path.starts_with(prefix)
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn get_root_path(&self) -> &str {
+        &self.root
+    }
+
+    pub fn get_root(&self) -> Option<Entry> {
+        self.storage.get(&self.root)
+    }
+
+    pub fn get_entry(&self, path: &str) -> Option<Entry> {
+        self.storage.get(&path.to_string())
+    }
+
+    /// Whether `path` is part of this scan. Its parent directory is
+    /// canonicalized like the root was, so relative paths and symlinked
+    /// parents resolve to their stored key, while a symlink stored as an
+    /// entry of its own is looked up as the link and not as its target.
+    pub fn contains_path<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        let resolved = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => {
+                let parent = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+                fs::canonicalize(parent).map(|parent| parent.join(name))
+            }
+            // Ends in `..` or is the file system root.
+            _ => fs::canonicalize(path),
+        };
+        let key = match resolved {
+            Ok(resolved) => format_path(&resolved),
+            Err(_) => format_path(path),
+        };
+        // Only the root itself was resolved all the way when stored.
+        self.storage.get(&key).is_some()
+            || fs::canonicalize(path).is_ok_and(|canonical| format_path(&canonical) == self.root)
+    }
+
+    /// Renders the storage key `path` according to `style`.
+    pub fn display_path(&self, path: &str, style: PathDisplay) -> String {
+        display_path(path, style, &self.root)
+    }
+
+    /// Storage keys of the root's direct children.
+    pub fn get_children(&self) -> Vec<String> {
+        match self.get_root() {
+            Some(Entry::Dir(dir)) => dir.get_children().to_vec(),
+            _ => vec![],
+        }
+    }
+
+    /// Direct children of the root with their name, type and size, sorted by
+    /// `key`. Built in one pass over storage rather than a lookup per child.
+    pub fn get_children_detailed(&self, key: ChildKey) -> Vec<ChildInfo> {
+        let entries = self.storage.values();
+        let dir_sizes = recursive_dir_sizes_of(&entries);
+        let mut children: Vec<ChildInfo> = entries
+            .iter()
+            .filter(|entry| entry.get_parent().as_deref() == Some(self.root.as_str()))
+            .map(|entry| {
+                let path = entry.get_format_path();
+                let size = match entry {
+                    Entry::Dir(_) => dir_sizes.get(&path).copied().unwrap_or(0),
+                    Entry::File(file) => file.get_size(),
+                };
+                ChildInfo {
+                    name: entry.get_name().to_string(),
+                    is_dir: entry.is_dir(),
+                    path,
+                    size,
+                }
+            })
+            .collect();
+        match key {
+            ChildKey::Name(order) => children.sort_by(|a, b| order.compare(&a.name, &b.name)),
+            ChildKey::Size => {
+                children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)))
+            }
+        }
+        children
+    }
+
+    /// Number of entries below the root, the root itself excluded.
+    ///
+    /// Falls back to a single threaded walk if the parallel one fails.
+    pub fn count_entries(&self) -> usize {
+        match self.get_root() {
+            Some(Entry::Dir(dir)) => dir
+                .count_entries_multi(self.storage.as_ref())
+                .unwrap_or_else(|_| dir.count_all_children(self.storage.as_ref())),
+            _ => 0,
+        }
+    }
+
+    /// Total size in bytes of every file below the root.
+    ///
+    /// Falls back to a single threaded walk if the parallel one fails.
+    pub fn calculate_size(&self) -> u64 {
+        match self.get_root() {
+            Some(Entry::Dir(dir)) => dir
+                .calculate_size_all_children_multi(self.storage.as_ref())
+                .unwrap_or_else(|_| dir.calculate_size_all_children(self.storage.as_ref())),
+            Some(Entry::File(file)) => file.get_size(),
+            None => 0,
+        }
+    }
+
+    /// Counts, sizes, largest files and extensions of everything stored, with
+    /// the error counts of the last `load_entry`.
+    pub fn build_report(&self) -> ScanReport {
+        let entries = self.storage.values();
+        let (mut files, mut dirs, mut time_errors) = (0, 0, 0);
+        for entry in &entries {
+            if !entry.get_time_errors().is_empty() {
+                time_errors += 1;
+            }
+            if entry.get_parent().is_none() {
+                continue;
+            }
+            match entry {
+                Entry::File(_) => files += 1,
+                Entry::Dir(_) => dirs += 1,
+            }
+        }
+
+        ScanReport {
+            root: self.root.clone(),
+            entries: files + dirs,
+            files,
+            dirs,
+            sizes: self.size_overview(),
+            top_files: self.largest_files(Some(ScanReport::TOP_FILES)),
+            extensions: self.size_by_extension().into_iter().collect(),
+            errors: self.errors_reported.load(Ordering::SeqCst),
+            errors_truncated: self.errors_truncated(),
+            time_errors,
+            truncated: self.is_truncated(),
+        }
+    }
+
+    /// Apparent, allocated and hardlink-deduplicated totals in a single pass.
+    pub fn size_overview(&self) -> SizeOverview {
+        let mut overview = SizeOverview::default();
+        let mut seen = HashSet::new();
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                overview.apparent += file.get_size();
+                let first_link = match file.get_inode() {
+                    Some(inode) => seen.insert(inode),
+                    None => true,
+                };
+                if first_link {
+                    overview.allocated += file.get_allocated_size();
+                    overview.dedup_apparent += file.get_size();
+                }
+            }
+        }
+        overview
+    }
+
+    /// Rough share of their size the files would keep once compressed, from
+    /// 0 to about 1, 1 meaning compression saves nothing.
+    ///
+    /// Only samples are read: a chunk from the start of the largest files
+    /// until `sample_bytes` were read in total. Each chunk is compressed with
+    /// zstd and its ratio weighted by the size of its whole file. Files that
+    /// can't be read are skipped, 1 is returned if nothing could be sampled.
+    #[cfg(feature = "compressibility")]
+    pub fn estimate_compressibility(&self, sample_bytes: usize) -> f64 {
+        use std::io::Read;
+
+        // Big enough for zstd to find repetitions, small enough to spread
+        // the budget over many files.
+        const CHUNK: usize = 64 * 1024;
+
+        let mut files: Vec<_> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) if !file.is_symlink() && file.get_size() > 0 => Some(file),
+                _ => None,
+            })
+            .collect();
+        files.sort_by_key(|file| Reverse(file.get_size()));
+
+        let (mut budget, mut total, mut compressed) = (sample_bytes, 0.0, 0.0);
+        for file in files {
+            if budget == 0 {
+                break;
+            }
+            let mut sample = vec![];
+            let read = fs::File::open(file.get_path())
+                .and_then(|f| f.take(budget.min(CHUNK) as u64).read_to_end(&mut sample));
+            if !read.is_ok_and(|n| n > 0) {
+                continue;
+            }
+            let packed = match zstd::bulk::compress(&sample, 0) {
+                Ok(packed) => packed,
+                Err(_) => continue,
+            };
+            budget -= sample.len();
+            let ratio = packed.len() as f64 / sample.len() as f64;
+            total += file.get_size() as f64;
+            compressed += file.get_size() as f64 * ratio;
+        }
+        if total == 0.0 {
+            1.0
+        } else {
+            compressed / total
+        }
+    }
+
+    /// Space the stored files would take on a file system allocating in
+    /// units of `block` bytes: each file's size rounded up to a multiple of
+    /// `block`, summed. A `block` of 0 sums the plain sizes.
+    pub fn size_with_block_size(&self, block: u64) -> u64 {
+        self.storage
+            .values()
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) if block > 0 => Some(file.get_size().div_ceil(block) * block),
+                Entry::File(file) => Some(file.get_size()),
+                Entry::Dir(_) => None,
+            })
+            .sum()
+    }
+
+    /// Recursive size of each of the root's direct children, largest first,
+    /// computed by walking the disk without keeping anything in storage.
+    /// Entries that can't be read are left out of the totals.
+    pub fn overview(&self) -> Vec<(String, u64)> {
+        match self.get_root() {
+            Some(Entry::Dir(mut dir)) => {
+                top_by_size(dir.child_subtree_sizes(&self.options).0, None)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Same as `overview`, walking several of the root's children at once
+    /// with the configured workers split between them.
+    pub fn overview_parallel(&self) -> Vec<(String, u64)> {
+        match self.get_root() {
+            Some(Entry::Dir(mut dir)) => {
+                top_by_size(dir.child_subtree_sizes_parallel(&self.options).0, None)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Every stored path, root included, in lexicographic order.
+    pub fn sorted_paths(&self) -> Vec<String> {
+        self.sorted_paths_by(SortOrder::Lexicographic)
+    }
+
+    /// Every stored path, root included, ordered by `order`.
+    pub fn sorted_paths_by(&self, order: SortOrder) -> Vec<String> {
+        let mut paths = self.storage.keys();
+        paths.sort_by(|a, b| order.compare(a, b));
+        paths
+    }
+
+    /// One `(path, size, is_dir)` row per stored entry below the root, sorted
+    /// by path, for table-like front ends. Directory sizes are recursive.
+    pub fn flat_listing(&self) -> Vec<(String, u64, bool)> {
+        let entries = self.storage.values();
+        let dir_sizes = recursive_dir_sizes_of(&entries);
+        let mut rows: Vec<(String, u64, bool)> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                let path = entry.get_format_path();
+                match entry {
+                    _ if path == self.root => None,
+                    _ if !self.options.reports_depth(entry.get_depth()) => None,
+                    Entry::Dir(_) => {
+                        let size = dir_sizes.get(&path).copied().unwrap_or(0);
+                        Some((path, size, true))
+                    }
+                    Entry::File(file) => Some((path, file.get_size(), false)),
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    }
+
+    /// Recursive size of each of the root's direct children, largest first.
+    pub fn child_sizes(&self) -> Vec<(String, u64)> {
+        let mut sizes: Vec<(String, u64)> = self
+            .get_children()
+            .into_iter()
+            .filter_map(|key| {
+                let size = match self.storage.get(&key)? {
+                    Entry::Dir(dir) => dir.calculate_size_all_children(self.storage.as_ref()),
+                    Entry::File(file) => file.get_size(),
+                };
+                Some((key, size))
+            })
+            .collect();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sizes
+    }
+
+    /// Same as `child_sizes`, but files hardlinked to each other count only
+    /// once, towards the first of them in path order, so totals add up to
+    /// what the tree really takes.
+    pub fn child_sizes_dedup(&self) -> Vec<(String, u64)> {
+        let mut sizes: HashMap<String, u64> = self
+            .get_children()
+            .into_iter()
+            .map(|key| (key, 0))
+            .collect();
+        let mut files: Vec<_> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) => {
+                    Some((file.get_format_path(), file.get_size(), file.get_inode()))
+                }
+                Entry::Dir(_) => None,
+            })
+            .collect();
+        files.sort();
+
+        let root = Path::new(&self.root);
+        let mut seen = HashSet::new();
+        for (path, size, inode) in files {
+            if inode.is_some_and(|inode| !seen.insert(inode)) {
+                continue;
+            }
+            let child = Path::new(&path)
+                .strip_prefix(root)
+                .ok()
+                .and_then(|rel| rel.components().next())
+                .map(|first| format_path(&root.join(first)));
+            if let Some(size_of_child) = child.and_then(|child| sizes.get_mut(&child)) {
+                *size_of_child += size;
+            }
+        }
+        top_by_size(sizes.into_iter().collect(), None)
+    }
+
+    /// Stored files sharing their inode with another stored file, sorted.
+    pub fn hardlinked_files(&self) -> Vec<String> {
+        let mut by_inode: HashMap<(u64, u64), Vec<String>> = HashMap::new();
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                if let Some(inode) = file.get_inode() {
+                    by_inode
+                        .entry(inode)
+                        .or_default()
+                        .push(file.get_format_path());
+                }
+            }
+        }
+        let mut linked: Vec<String> = by_inode
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .flatten()
+            .collect();
+        linked.sort();
+        linked
+    }
+
+    /// Total size and file count per lowercased extension, extensionless files are skipped.
+    pub fn size_by_extension(&self) -> HashMap<String, (u64, usize)> {
+        let mut by_extension: HashMap<String, (u64, usize)> = HashMap::new();
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                if let Some(ext) = lowercase_extension(&file) {
+                    let group = by_extension.entry(ext).or_insert((0, 0));
+                    group.0 += file.get_size();
+                    group.1 += 1;
+                }
+            }
+        }
+        by_extension
+    }
+
+    /// Total size and file count for the single extension `ext`, compared
+    /// case-insensitively, without building the full per-extension map.
+    pub fn size_of_extension(&self, ext: &str) -> (u64, usize) {
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        let mut total = (0, 0);
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                if lowercase_extension(&file).as_ref() == Some(&ext) {
+                    total.0 += file.get_size();
+                    total.1 += 1;
+                }
+            }
+        }
+        total
+    }
+
+    /// Total size of the files with each of the extensions in `exts`, compared
+    /// case-insensitively and keyed by the lowercased extension. Other
+    /// extensions are skipped.
+    ///
+    /// Only the keys are listed up front, fetching every entry from storage
+    /// and classifying it is spread over the configured workers, which pays
+    /// off with storages that decode entries on `get`. Fails if a worker
+    /// panicked, since the totals would be incomplete.
+    pub fn size_of_extensions_parallel(
+        &self,
+        exts: &[&str],
+    ) -> Result<HashMap<String, u64>, GenericError> {
+        let wanted: Vec<String> = exts
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect();
+        let (sizes, mut panics) = run_parallel_with_state(
+            &self.options,
+            self.storage.keys(),
+            || vec![0; wanted.len()],
+            |key: String, sizes: &mut Vec<u64>, _| {
+                if let Some(Entry::File(file)) = self.storage.get(&key) {
+                    let ext = lowercase_extension(&file);
+                    if let Some(i) = ext.and_then(|ext| wanted.iter().position(|w| *w == ext)) {
+                        sizes[i] += file.get_size();
+                    }
+                }
+            },
+        );
+        if let Some(e) = panics.pop() {
+            return Err(e);
+        }
+
+        let mut totals: HashMap<String, u64> = wanted.iter().map(|ext| (ext.clone(), 0)).collect();
+        for worker in sizes {
+            for (ext, size) in wanted.iter().zip(worker) {
+                *totals.get_mut(ext).unwrap() += size;
+            }
+        }
+        Ok(totals)
+    }
+
+    /// Distinct lowercased file extensions, sorted.
+    pub fn extensions(&self) -> Vec<String> {
+        let mut extensions: Vec<String> = self.size_by_extension().into_keys().collect();
+        extensions.sort();
+        extensions
+    }
+
+    pub fn extension_count(&self) -> usize {
+        self.size_by_extension().len()
+    }
+
+    /// Every stored file sorted into a tier by `SizeTiers::MEDIUM` and
+    /// `SizeTiers::BIG`, see `size_tiers_with`.
+    pub fn size_tiers(&self) -> SizeTiers {
+        self.size_tiers_with(SizeTiers::MEDIUM, SizeTiers::BIG)
+    }
+
+    /// Every stored file sorted into a tier: big from `big` bytes up, medium
+    /// from `medium` bytes up and small below that.
+    pub fn size_tiers_with(&self, medium: u64, big: u64) -> SizeTiers {
+        let mut tiers = SizeTiers::default();
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                let tier = match file.get_size() {
+                    size if size >= big => &mut tiers.big,
+                    size if size >= medium => &mut tiers.medium,
+                    _ => &mut tiers.small,
+                };
+                tier.push(file.get_format_path());
+            }
+        }
+        tiers.big.sort();
+        tiers.medium.sort();
+        tiers.small.sort();
+        tiers
+    }
+
+    /// Total size and file count per `FileCategory`, files without a known
+    /// extension count as `FileCategory::Other`.
+    pub fn size_by_category(&self) -> HashMap<FileCategory, (u64, usize)> {
+        let mut by_category: HashMap<FileCategory, (u64, usize)> = HashMap::new();
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                let group = by_category.entry(file.category()).or_insert((0, 0));
+                group.0 += file.get_size();
+                group.1 += 1;
+            }
+        }
+        by_category
+    }
+
+    /// Entries added and removed in `newer`, a later scan of the same tree.
+    /// A removed file and an added one sharing an inode, size and modification
+    /// time are reported as a single `TreeChange::Moved`. Moves come first,
+    /// then additions and removals, each sorted by path.
+    pub fn diff(&self, newer: &EntryWrapper) -> Vec<TreeChange> {
+        let old_paths: HashSet<String> = self.storage.keys().into_iter().collect();
+        let new_paths: HashSet<String> = newer.storage.keys().into_iter().collect();
+        let mut removed: Vec<String> = old_paths.difference(&new_paths).cloned().collect();
+        let mut added: Vec<String> = new_paths.difference(&old_paths).cloned().collect();
+        removed.sort();
+        added.sort();
+
+        // Inodes get reused, so a freed one only counts as the same file if
+        // its size and modification time match too.
+        let identity = |wrapper: &EntryWrapper, path: &String| match wrapper.storage.get(path) {
+            Some(Entry::File(file)) => file
+                .get_inode()
+                .map(|inode| (inode, file.get_size(), file.get_modified())),
+            _ => None,
+        };
+        let mut removed_by_identity = HashMap::new();
+        for path in &removed {
+            if let Some(id) = identity(self, path) {
+                removed_by_identity
+                    .entry(id)
+                    .or_insert_with(|| path.clone());
+            }
+        }
+
+        let mut changes = vec![];
+        let mut moved_from = HashSet::new();
+        added.retain(|to| {
+            let from = identity(newer, to).and_then(|id| removed_by_identity.remove(&id));
+            match from {
+                Some(from) => {
+                    moved_from.insert(from.clone());
+                    changes.push(TreeChange::Moved {
+                        from,
+                        to: to.clone(),
+                    });
+                    false
+                }
+                None => true,
+            }
+        });
+        changes.extend(added.into_iter().map(TreeChange::Added));
+        changes.extend(
+            removed
+                .into_iter()
+                .filter(|path| !moved_from.contains(path))
+                .map(TreeChange::Removed),
+        );
+        changes
+    }
+
+    /// Net change in total size from `old` to `new`, a later scan of the same
+    /// tree. Negative when space was freed.
+    pub fn space_reclaimed(old: &EntryWrapper, new: &EntryWrapper) -> i64 {
+        new.calculate_size() as i64 - old.calculate_size() as i64
+    }
+
+    /// Entries of `old` that are gone from `new` by `diff`, largest first
+    /// with their size in `old`, at most `limit` of them. Moved entries don't
+    /// count and a removed directory stands in for everything below it.
+    pub fn largest_removed(
+        old: &EntryWrapper,
+        new: &EntryWrapper,
+        limit: Option<usize>,
+    ) -> Vec<(String, u64)> {
+        let removed: HashSet<String> = old
+            .diff(new)
+            .into_iter()
+            .filter_map(|change| match change {
+                TreeChange::Removed(path) => Some(path),
+                _ => None,
+            })
+            .collect();
+        let dir_sizes = old.recursive_dir_sizes();
+        let sizes = removed
+            .iter()
+            .filter_map(|path| {
+                let entry = old.storage.get(path)?;
+                if entry
+                    .get_parent()
+                    .is_some_and(|parent| removed.contains(&parent))
+                {
+                    return None;
+                }
+                let size = match entry {
+                    Entry::Dir(_) => dir_sizes.get(path).copied().unwrap_or(0),
+                    Entry::File(file) => file.get_size(),
+                };
+                Some((path.clone(), size))
+            })
+            .collect();
+        top_by_size(sizes, limit)
+    }
+
+    /// Groups of stored paths that only differ by case and would collide on a
+    /// case-insensitive file system. Groups and their members are sorted.
+    pub fn find_case_collisions(&self) -> Vec<Vec<String>> {
+        let mut by_folded: HashMap<String, Vec<String>> = HashMap::new();
+        for path in self.storage.keys() {
+            by_folded.entry(path.to_lowercase()).or_default().push(path);
+        }
+
+        let mut collisions: Vec<Vec<String>> = by_folded
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort();
+                paths
+            })
+            .collect();
+        collisions.sort();
+        collisions
+    }
+
+    /// Files owned by `uid` with their sizes, largest first.
+    pub fn find_by_owner(&self, uid: u32) -> Vec<(String, u64)> {
+        let files = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) if file.get_uid() == Some(uid) => {
+                    Some((file.get_format_path(), file.get_size()))
+                }
+                _ => None,
+            })
+            .collect();
+        top_by_size(files, None)
+    }
+
+    /// Total file size per owning user id, empty on platforms without ownership.
+    pub fn size_by_owner(&self) -> HashMap<u32, u64> {
+        let mut by_owner = HashMap::new();
+        for entry in self.storage.values() {
+            if let Entry::File(file) = entry {
+                if let Some(uid) = file.get_uid() {
+                    *by_owner.entry(uid).or_insert(0) += file.get_size();
+                }
+            }
+        }
+        by_owner
+    }
+
+    /// Paths of every executable file, sorted.
+    pub fn find_executables(&self) -> Vec<String> {
+        let mut executables: Vec<String> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) if file.is_executable() => Some(file.get_format_path()),
+                _ => None,
+            })
+            .collect();
+        executables.sort();
+        executables
+    }
+
+    /// Files and directories that anyone may write to, or that run with the
+    /// rights of their owner or group. Symlinks are left out since they
+    /// always carry every permission. Empty on platforms without Unix modes.
+    pub fn permission_audit(&self) -> PermissionAudit {
+        const SETUID: u32 = 0o4000;
+        const SETGID: u32 = 0o2000;
+
+        let mut audit = PermissionAudit::default();
+        for entry in self.storage.values() {
+            let mode = match entry.get_mode() {
+                Some(mode) if !entry.is_symlink() => mode,
+                _ => continue,
+            };
+            let path = entry.get_format_path();
+            if entry.is_world_writable() {
+                audit.world_writable.push(path.clone());
+            }
+            if mode & SETUID != 0 {
+                audit.setuid.push(path.clone());
+            }
+            if mode & SETGID != 0 {
+                audit.setgid.push(path);
+            }
+        }
+        audit.world_writable.sort();
+        audit.setuid.sort();
+        audit.setgid.sort();
+        audit
+    }
+
+    /// Paths of the entries whose Unix mode satisfies `predicate`, sorted.
+    /// Symlinks are left out, and everything on platforms without Unix modes.
+    pub fn find_by_mode<F>(&self, predicate: F) -> Vec<String>
+    where
+        F: Fn(u32) -> bool,
+    {
+        let mut paths: Vec<String> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter(|entry| !entry.is_symlink() && entry.get_mode().is_some_and(&predicate))
+            .map(|entry| entry.get_format_path())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Paths of entries selected by `matcher`, sorted, at most the first
+    /// `limit` of them. With a limit only that many matches are held at a
+    /// time, so a small one stays cheap on a huge tree.
+    pub fn find(&self, matcher: &dyn Matcher, limit: Option<usize>) -> Vec<String> {
+        self.find_by(matcher, limit, SortOrder::Lexicographic)
+    }
+
+    /// Same as `find`, ordered by `order` before `limit` applies.
+    pub fn find_by(
+        &self,
+        matcher: &dyn Matcher,
+        limit: Option<usize>,
+        order: SortOrder,
+    ) -> Vec<String> {
+        let matches = self
+            .storage
+            .values()
+            .into_iter()
+            .filter(|entry| matcher.matches(entry) && self.options.reports_depth(entry.get_depth()))
+            .map(|entry| entry.get_format_path());
+
+        let limit = match limit {
+            Some(limit) => limit,
+            None => {
+                let mut found: Vec<String> = matches.collect();
+                found.sort_by(|a, b| order.compare(a, b));
+                return found;
+            }
+        };
+        // Max-heap on `order`, so the path sorting last is the one dropped.
+        let mut heap = BinaryHeap::with_capacity(limit + 1);
+        for path in matches {
+            heap.push(OrderedPath(path, order));
+            if heap.len() > limit {
+                heap.pop();
+            }
+        }
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|OrderedPath(path, _)| path)
+            .collect()
+    }
+
+    /// Summed size of the stored entries `matcher` selects. Directories count
+    /// as 0, so only matching files add up.
+    pub fn size_where(&self, matcher: &dyn Matcher) -> u64 {
+        self.storage
+            .values()
+            .iter()
+            .filter(|entry| matcher.matches(entry))
+            .map(Entry::get_size)
+            .sum()
+    }
+
+    /// Files ordered from the largest, at most `limit` of them.
+    pub fn largest_files(&self, limit: Option<usize>) -> Vec<(String, u64)> {
+        let files = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::File(file) if self.options.reports_depth(file.get_depth()) => {
+                    Some((file.get_format_path(), file.get_size()))
+                }
+                _ => None,
+            })
+            .collect();
+        top_by_size(files, limit)
+    }
+
+    /// Directories where a single direct child accounts for more than `ratio`
+    /// of the recursive size, as `(dir, child, fraction)` with the most
+    /// lopsided first. Empty directories are never reported.
+    pub fn top_heavy_dirs(&self, ratio: f64) -> Vec<(String, String, f64)> {
+        let entries = self.storage.values();
+        let dir_sizes = recursive_dir_sizes_of(&entries);
+
+        let mut largest_child: HashMap<String, (String, u64)> = HashMap::new();
+        for entry in &entries {
+            let parent = match entry.get_parent() {
+                Some(parent) => parent,
+                None => continue,
+            };
+            let path = entry.get_format_path();
+            let size = match entry {
+                Entry::Dir(_) => dir_sizes.get(&path).copied().unwrap_or(0),
+                Entry::File(file) => file.get_size(),
+            };
+            let largest = largest_child.entry(parent).or_insert((String::new(), 0));
+            if size > largest.1 || (size == largest.1 && (largest.0.is_empty() || path < largest.0))
+            {
+                *largest = (path, size);
+            }
+        }
+
+        let mut heavy: Vec<(String, String, f64)> = largest_child
+            .into_iter()
+            .filter_map(|(dir, (child, size))| {
+                let total = dir_sizes.get(&dir).copied().unwrap_or(0);
+                if total == 0 {
+                    return None;
+                }
+                let fraction = size as f64 / total as f64;
+                if fraction > ratio {
+                    Some((dir, child, fraction))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        heavy.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        heavy
+    }
+
+    /// Directories ordered from the largest recursive size, at most `limit` of them.
+    pub fn largest_dirs(&self, limit: Option<usize>) -> Vec<(String, u64)> {
+        let mut sizes = self.recursive_dir_sizes();
+        if self.options.min_depth.is_some() {
+            sizes.retain(|path, _| {
+                self.storage
+                    .get(path)
+                    .is_some_and(|entry| self.options.reports_depth(entry.get_depth()))
+            });
+        }
+        top_by_size(sizes.into_iter().collect(), limit)
+    }
+
+    /// Every stored directory ranked by how many entries are below it, most
+    /// first. Each entry takes about one inode, so this shows where inodes
+    /// go on file systems that run out of them before running out of space.
+    pub fn inode_usage_by_dir(&self) -> Vec<(String, u64)> {
+        let usage = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Dir(dir) => Some((
+                    dir.get_format_path(),
+                    dir.count_all_children(self.storage.as_ref()) as u64,
+                )),
+                Entry::File(_) => None,
+            })
+            .collect();
+        top_by_size(usage, None)
+    }
+
+    /// The whole scan as one JSON object: `root` holds the root's key and
+    /// `entries` every stored entry keyed by path, in path order. Entries
+    /// refer to their parent and children by key only. Times that couldn't
+    /// be read are `null`.
+    pub fn to_json(&self) -> Result<String, GenericError> {
+        Ok(serde_json::to_string(&self.saved_scan())?)
+    }
+
+    /// Writes the scan to the file at `path` in the format of `to_json`, read
+    /// it back with `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GenericError> {
+        let path = path.as_ref();
+        let with_path = |e: &dyn fmt::Display| format!("{}: {}", path.display(), e);
+        let file = fs::File::create(path).map_err(|e| with_path(&e))?;
+        let mut w = io::BufWriter::new(file);
+        serde_json::to_writer(&mut w, &self.saved_scan()).map_err(|e| with_path(&e))?;
+        w.flush().map_err(|e| with_path(&e))?;
+        Ok(())
+    }
+
+    fn saved_scan(&self) -> SavedScan {
+        let entries = self
+            .storage
+            .values()
+            .into_iter()
+            .map(|entry| (entry.get_format_path(), entry))
+            .collect();
+        SavedScan {
+            root: self.root.clone(),
+            entries,
+        }
+    }
+
+    /// Writes every stored directory with its recursive size and entry count
+    /// as JSON, leaving out the files. Read it back with `DirSkeleton::from_json`.
+    pub fn to_json_dirs_only<W: Write>(&self, w: W) -> Result<(), GenericError> {
+        let entries = self.storage.values();
+        let sizes = recursive_dir_sizes_of(&entries);
+        let counts: HashMap<String, u64> = self.inode_usage_by_dir().into_iter().collect();
+        let mut dirs: Vec<DirSummary> = entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Dir(dir) => {
+                    let path = dir.get_format_path();
+                    Some(DirSummary {
+                        size: sizes.get(&path).copied().unwrap_or(0),
+                        entries: counts.get(&path).copied().unwrap_or(0),
+                        parent: dir.get_parent(),
+                        depth: dir.get_depth(),
+                        path,
+                    })
+                }
+                Entry::File(_) => None,
+            })
+            .collect();
+        dirs.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let skeleton = DirSkeleton {
+            root: self.root.clone(),
+            dirs,
+        };
+        serde_json::to_writer(w, &skeleton)?;
+        Ok(())
+    }
+
+    /// Parent/children adjacency of everything stored, to be kept next to
+    /// the storage for navigating without reading entries.
+    pub fn topology(&self) -> TopologyIndex {
+        TopologyIndex::from_storage(self.storage.as_ref())
+    }
+
+    /// Writes the stored tree as a Graphviz digraph, one node per entry
+    /// labelled with its name and size (recursive for directories) and an
+    /// edge from every directory to each child. Fails without writing
+    /// anything if more than `max_entries` entries are stored, since larger
+    /// graphs are unreadable.
+    pub fn export_dot<W: Write>(&self, w: W, max_entries: usize) -> Result<(), GenericError> {
+        self.export_dot_collapsed(w, max_entries, 0)
+    }
+
+    /// Same as `export_dot`, but within each directory the files smaller
+    /// than `small_file_threshold` bytes are drawn as one
+    /// `(many small files)` node with their count and combined size.
+    /// `max_entries` limits the nodes left after collapsing.
+    pub fn export_dot_collapsed<W: Write>(
+        &self,
+        mut w: W,
+        max_entries: usize,
+        small_file_threshold: u64,
+    ) -> Result<(), GenericError> {
+        let nodes = tree_nodes(self.storage.values(), small_file_threshold);
+        if nodes.len() > max_entries {
+            return Err(format!(
+                "{} entries are too many for a graph, the limit is {}",
+                nodes.len(),
+                max_entries
+            )
+            .into());
+        }
+        let ids: HashMap<&str, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| (node.path.as_str(), id))
+            .collect();
+
+        writeln!(w, "digraph drfs {{")?;
+        for (id, node) in nodes.iter().enumerate() {
+            let (shape, name) = match node.kind {
+                TreeNodeKind::Dir => ("folder", dot_escape(&node.name)),
+                TreeNodeKind::File => ("note", dot_escape(&node.name)),
+                TreeNodeKind::SmallFiles(count) => {
+                    ("box3d", format!("{} ({} files)", node.name, count))
+                }
+            };
+            writeln!(
+                w,
+                "    n{} [shape={}, label=\"{}\\n{}\"];",
+                id,
+                shape,
+                name,
+                format_size(node.size)
+            )?;
+        }
+        for (id, node) in nodes.iter().enumerate() {
+            if let Some(parent) = node.parent.as_deref().and_then(|parent| ids.get(parent)) {
+                writeln!(w, "    n{} -> n{};", parent, id)?;
+            }
+        }
+        writeln!(w, "}}")?;
+        Ok(())
+    }
+
+    /// Direct child count and summed direct file size for every stored
+    /// directory, grouped in a single storage walk.
+    pub fn group_by_parent(&self) -> HashMap<String, (usize, u64)> {
+        let mut groups: HashMap<String, (usize, u64)> = HashMap::new();
+        for entry in self.storage.values() {
+            if entry.is_dir() {
+                groups.entry(entry.get_format_path()).or_insert((0, 0));
+            }
+            if let Some(parent) = entry.get_parent() {
+                let group = groups.entry(parent).or_insert((0, 0));
+                group.0 += 1;
+                group.1 += entry.get_size();
+            }
+        }
+        groups
+    }
+
+    /// Recursive size of every stored directory, computed in one storage walk.
+    fn recursive_dir_sizes(&self) -> HashMap<String, u64> {
+        recursive_dir_sizes_of(&self.storage.values())
+    }
+
+    /// Size of the largest file anywhere below the stored directory `path`,
+    /// 0 if there is none. `None` if `path` isn't a stored directory.
+    pub fn max_child_file_size(&self, path: &str) -> Option<u64> {
+        self.with_max_file_sizes(|sizes| sizes.get(path).copied())
+    }
+
+    /// Directories with a file larger than `threshold` bytes somewhere in
+    /// their subtree, sorted. Every ancestor of such a file is included.
+    pub fn dirs_with_large_files(&self, threshold: u64) -> Vec<String> {
+        let mut dirs: Vec<String> = self.with_max_file_sizes(|sizes| {
+            sizes
+                .iter()
+                .filter(|(_, max)| **max > threshold)
+                .map(|(dir, _)| dir.clone())
+                .collect()
+        });
+        dirs.sort();
+        dirs
+    }
+
+    /// Forgets everything computed from storage, for after it changed.
+    fn drop_caches(&self) {
+        self.max_file_sizes.write().unwrap().take();
+        self.structure_hash.write().unwrap().take();
+    }
+
+    /// Digest of every stored path with its size and modification time.
+    /// Scans of an unchanged tree hash the same, so a matching hash means a
+    /// `diff` would come back empty. Computed once per change to storage.
+    ///
+    /// The hash is 64-bit FNV-1a over each entry's path bytes, size and
+    /// modification time since the UNIX epoch, so it can be stored and
+    /// compared across builds and platforms. Blind to modification times if
+    /// the scans didn't collect them.
+    pub fn structure_hash(&self) -> u64 {
+        if let Some(hash) = *self.structure_hash.read().unwrap() {
+            return hash;
+        }
+        let mut cache = self.structure_hash.write().unwrap();
+        *cache.get_or_insert_with(|| {
+            let mut entries: Vec<(String, u64, Option<SystemTime>)> = self
+                .storage
+                .values()
+                .into_iter()
+                .map(|entry| {
+                    (
+                        entry.get_format_path(),
+                        entry.get_size(),
+                        entry.get_modified(),
+                    )
+                })
+                .collect();
+            entries.sort();
+            let mut hash = Fnv1a::new();
+            for (path, size, modified) in entries {
+                hash.write(path.as_bytes());
+                hash.write(&[0]);
+                hash.write(&size.to_le_bytes());
+                match modified.map(|time| time.duration_since(UNIX_EPOCH)) {
+                    Some(Ok(since)) => {
+                        hash.write(&[1]);
+                        hash.write(&since.as_secs().to_le_bytes());
+                        hash.write(&since.subsec_nanos().to_le_bytes());
+                    }
+                    Some(Err(before)) => {
+                        let before = before.duration();
+                        hash.write(&[2]);
+                        hash.write(&before.as_secs().to_le_bytes());
+                        hash.write(&before.subsec_nanos().to_le_bytes());
+                    }
+                    None => hash.write(&[0]),
+                }
+            }
+            hash.finish()
+        })
+    }
+
+    fn with_max_file_sizes<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&HashMap<String, u64>) -> R,
+    {
+        if let Some(sizes) = self.max_file_sizes.read().unwrap().as_ref() {
+            return f(sizes);
+        }
+        let mut cache = self.max_file_sizes.write().unwrap();
+        f(cache.get_or_insert_with(|| self.recursive_max_file_sizes()))
+    }
+
+    /// Largest file below every stored directory, computed in one storage walk.
+    fn recursive_max_file_sizes(&self) -> HashMap<String, u64> {
+        let entries = self.storage.values();
+        let mut parents: HashMap<String, Option<String>> = HashMap::new();
+        let mut maxima: HashMap<String, u64> = HashMap::new();
+        for entry in &entries {
+            if entry.is_dir() {
+                parents.insert(entry.get_format_path(), entry.get_parent());
+                maxima.insert(entry.get_format_path(), 0);
+            }
+        }
+        for entry in &entries {
+            if let Entry::File(file) = entry {
+                let mut parent = file.get_parent();
+                while let Some(dir) = parent {
+                    match maxima.get_mut(&dir) {
+                        // Ancestors already hold at least this much.
+                        Some(max) if *max >= file.get_size() => break,
+                        Some(max) => *max = file.get_size(),
+                        None => break,
+                    }
+                    parent = parents.get(&dir).cloned().flatten();
+                }
+            }
+        }
+        maxima
+    }
+
+    /// Up to `n` directories that took the longest to read, slowest first.
+    /// Empty unless the scan ran with `ScanOptions::time_reads`.
+    pub fn slowest_dirs(&self, n: usize) -> Vec<(String, Duration)> {
+        let mut dirs: Vec<(String, Duration)> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                Entry::Dir(dir) => dir
+                    .get_read_time()
+                    .map(|time| (dir.get_format_path(), time)),
+                Entry::File(_) => None,
+            })
+            .collect();
+        dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        dirs.truncate(n);
+        dirs
+    }
+
+    /// Returns up to `n` directories ordered from the most deeply nested,
+    /// as `(path, depth)` with the root at depth 0.
+    pub fn deepest_dirs(&self, n: usize) -> Vec<(String, usize)> {
+        let mut dirs: Vec<(String, usize)> = self
+            .storage
+            .values()
+            .into_iter()
+            .filter(|entry| entry.is_dir() && self.options.reports_depth(entry.get_depth()))
+            .map(|entry| (entry.get_format_path(), entry.get_depth()))
+            .collect();
+        dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        dirs.truncate(n);
+        dirs
+    }
+}
+
+/// Core of `EntryWrapper::size_exceeds`, `visitor` sees every entry walked.
+fn size_exceeds_with<P, F>(root: P, threshold: u64, options: &ScanOptions, visitor: F) -> bool
+where
+    P: AsRef<Path>,
+    F: Fn(&Entry) + Sync,
+{
+    let mut dir = match Entry::new(root) {
+        Ok(Entry::Dir(dir)) => dir,
+        Ok(Entry::File(file)) => return file.get_size() > threshold,
+        Err(_) => return false,
+    };
+    // The byte budget is what stops the walk once the threshold is crossed.
+    let options = ScanOptions {
+        max_bytes: Some(threshold),
+        ..options.clone()
+    };
+    let total = AtomicU64::new(0);
+    dir.visit_all_children(&options, |entry| {
+        visitor(entry);
+        total.fetch_add(entry.get_size(), Ordering::Relaxed);
+    });
+    total.into_inner() > threshold
+}
+
+/// Recursive size of every directory among `entries`.
+fn recursive_dir_sizes_of(entries: &[Entry]) -> HashMap<String, u64> {
+    let mut parents: HashMap<String, Option<String>> = HashMap::new();
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    for entry in entries {
+        if entry.is_dir() {
+            parents.insert(entry.get_format_path(), entry.get_parent());
+            sizes.insert(entry.get_format_path(), 0);
+        }
+    }
+    for entry in entries {
+        if let Entry::File(file) = entry {
+            let mut parent = file.get_parent();
+            while let Some(dir) = parent {
+                match sizes.get_mut(&dir) {
+                    Some(size) => *size += file.get_size(),
+                    None => break,
+                }
+                parent = parents.get(&dir).cloned().flatten();
+            }
+        }
+    }
+    sizes
+}
+
+#[cfg(unix)]
+type RootId = (u64, u64);
+
+#[cfg(not(unix))]
+type RootId = PathBuf;
+
+/// `(device, inode)` of the directory `root` resolves to.
+#[cfg(unix)]
+fn root_id(root: &Path) -> Option<RootId> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(root).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn root_id(root: &Path) -> Option<RootId> {
+    fs::canonicalize(root).ok()
+}
+
+/// What a `TreeNode` stands for, aggregates carry their file count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TreeNodeKind {
+    Dir,
+    File,
+    SmallFiles(u64),
+}
+
+/// One node of a drawn tree, sizes of directories are recursive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TreeNode {
+    path: String,
+    parent: Option<String>,
+    name: String,
+    size: u64,
+    kind: TreeNodeKind,
+}
+
+/// Nodes for `entries` in path order. Where a directory holds more than one
+/// file smaller than `small_file_threshold`, those files are replaced by a
+/// single aggregate node listed after all entries.
+fn tree_nodes(mut entries: Vec<Entry>, small_file_threshold: u64) -> Vec<TreeNode> {
+    entries.sort_by_key(Entry::get_format_path);
+    let dir_sizes = recursive_dir_sizes_of(&entries);
+
+    let mut small: HashMap<String, Vec<TreeNode>> = HashMap::new();
+    let mut nodes = vec![];
+    for entry in &entries {
+        let path = entry.get_format_path();
+        let (size, kind) = match entry {
+            Entry::Dir(_) => (
+                dir_sizes.get(&path).copied().unwrap_or(0),
+                TreeNodeKind::Dir,
+            ),
+            Entry::File(file) => (file.get_size(), TreeNodeKind::File),
+        };
+        let node = TreeNode {
+            parent: entry.get_parent(),
+            name: entry.get_name().to_string(),
+            path,
+            size,
+            kind,
+        };
+        match &node.parent {
+            Some(parent) if kind == TreeNodeKind::File && size < small_file_threshold => {
+                small.entry(parent.clone()).or_default().push(node)
+            }
+            _ => nodes.push(node),
+        }
+    }
+
+    let mut collapsed: Vec<TreeNode> = vec![];
+    for (parent, files) in small {
+        if files.len() < 2 {
+            nodes.extend(files);
+            continue;
+        }
+        collapsed.push(TreeNode {
+            path: format!("{}/(many small files)", parent),
+            name: "(many small files)".to_string(),
+            size: files.iter().map(|file| file.size).sum(),
+            kind: TreeNodeKind::SmallFiles(files.len() as u64),
+            parent: Some(parent),
+        });
+    }
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    collapsed.sort_by(|a, b| a.path.cmp(&b.path));
+    nodes.extend(collapsed);
+    nodes
+}
+
+/// Escapes `s` for use inside a quoted DOT string.
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Sorts by size descending, ties by path, and keeps at most `limit` items.
+fn top_by_size(mut items: Vec<(String, u64)>, limit: Option<usize>) -> Vec<(String, u64)> {
+    items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if let Some(limit) = limit {
+        items.truncate(limit);
+    }
+    items
+}
+
+/// A path ordered by its `SortOrder`, for keeping the first few in a heap.
+struct OrderedPath(String, SortOrder);
+
+impl Ord for OrderedPath {
+    fn cmp(&self, other: &OrderedPath) -> cmp::Ordering {
+        self.1.compare(&self.0, &other.0)
+    }
+}
+
+impl PartialOrd for OrderedPath {
+    fn partial_cmp(&self, other: &OrderedPath) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OrderedPath {
+    fn eq(&self, other: &OrderedPath) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for OrderedPath {}
+
+/// 64-bit FNV-1a, for digests that have to stay the same across builds.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The one extension every per-extension query groups files by.
+fn lowercase_extension(file: &FileEntry) -> Option<String> {
+    file.get_extension().map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::dir::test_seam::DENIED_PREFIX;
+    use crate::matcher::{Extensions, Glob, MinSize};
+    use crate::test_util::{make_dir, write_file};
+    use crate::walk::ReadTimer;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn load_counts_and_sizes() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/b.txt", 20);
+        write_file(tmp.path(), "sub/deeper/c.txt", 30);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        assert!(wrapper.load_entry().is_empty());
+
+        assert_eq!(wrapper.count_entries(), 5);
+        assert_eq!(wrapper.calculate_size(), 60);
+        assert_eq!(wrapper.get_children().len(), 2);
+    }
+
+    #[test]
+    fn deepest_dirs_ranks_deep_branch_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        make_dir(tmp.path(), "shallow");
+        make_dir(tmp.path(), "deep/a/b/c");
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let deepest = wrapper.deepest_dirs(2);
+        let root = wrapper.get_root_path();
+        assert_eq!(deepest[0], (format!("{}/deep/a/b/c", root), 4));
+        assert_eq!(deepest[1], (format!("{}/deep/a/b", root), 3));
+    }
+
+    #[test]
+    fn entries_record_scan_time() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "sub/a.txt", 1);
+
+        let before = SystemTime::now();
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let after = SystemTime::now();
+
+        let file = format!("{}/sub/a.txt", wrapper.get_root_path());
+        let scanned_at = wrapper.get_entry(&file).unwrap().get_scanned_at();
+        assert!(scanned_at >= before && scanned_at <= after);
+        assert!(after.duration_since(scanned_at).unwrap() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn child_sizes_are_recursive_and_sorted() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "small.txt", 5);
+        write_file(tmp.path(), "big/a.txt", 50);
+        write_file(tmp.path(), "big/nested/b.txt", 50);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.child_sizes(),
+            vec![
+                (format!("{}/big", root), 100),
+                (format!("{}/small.txt", root), 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn extensions_are_case_insensitive() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "B.TXT", 2);
+        write_file(tmp.path(), "src/main.rs", 3);
+        write_file(tmp.path(), "Makefile", 4);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        assert_eq!(wrapper.extension_count(), 2);
+        assert_eq!(wrapper.extensions(), vec!["rs", "txt"]);
+        assert_eq!(wrapper.size_by_extension()["txt"], (3, 2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn find_executables_uses_mode_bits() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let script = write_file(tmp.path(), "run.sh", 1);
+        let data = write_file(tmp.path(), "data.txt", 1);
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&data, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(wrapper.find_executables(), vec![format!("{}/run.sh", root)]);
+    }
+
+    #[test]
+    fn sorted_paths_lists_everything_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "b/2.txt", 1);
+        write_file(tmp.path(), "b/1.txt", 1);
+        write_file(tmp.path(), "a.txt", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.sorted_paths(),
+            vec![
+                root.to_string(),
+                format!("{}/a.txt", root),
+                format!("{}/b", root),
+                format!("{}/b/1.txt", root),
+                format!("{}/b/2.txt", root),
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_largest_files_matches_stored_scan() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.bin", 50);
+        write_file(tmp.path(), "b.bin", 10);
+        write_file(tmp.path(), "sub/c.bin", 70);
+        write_file(tmp.path(), "sub/d.bin", 50);
+        write_file(tmp.path(), "sub/deeper/e.bin", 5);
+        write_file(tmp.path(), "sub/deeper/f.bin", 90);
+        #[cfg(unix)]
+        {
+            // Links count at their own size and a loop back up is not walked.
+            std::os::unix::fs::symlink("sub/deeper/f.bin", tmp.path().join("link.bin")).unwrap();
+            std::os::unix::fs::symlink("../..", tmp.path().join("sub/deeper/up")).unwrap();
+        }
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        for n in 0..10 {
+            assert_eq!(
+                EntryWrapper::stream_largest_files(tmp.path(), n),
+                wrapper.largest_files(Some(n)),
+            );
+        }
+    }
+
+    #[test]
+    fn min_depth_hides_shallow_entries_from_reports() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "top.txt", 100);
+        write_file(tmp.path(), "sub/nested.txt", 10);
+        write_file(tmp.path(), "sub/deeper/leaf.txt", 1);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            min_depth: Some(2),
+            ..ScanOptions::default()
+        });
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path().to_string();
+        assert_eq!(
+            wrapper.largest_files(None),
+            vec![
+                (format!("{}/sub/nested.txt", root), 10),
+                (format!("{}/sub/deeper/leaf.txt", root), 1),
+            ]
+        );
+        assert_eq!(
+            wrapper.largest_dirs(None),
+            vec![(format!("{}/sub/deeper", root), 1)]
+        );
+        assert_eq!(
+            wrapper.find(&Glob::new("*.txt").unwrap(), None),
+            vec![
+                format!("{}/sub/deeper/leaf.txt", root),
+                format!("{}/sub/nested.txt", root),
+            ]
+        );
+        assert_eq!(wrapper.flat_listing().len(), 3);
+        // Still traversed and counted.
+        assert_eq!(wrapper.calculate_size(), 111);
+    }
+
+    #[test]
+    fn parallel_overview_matches_sequential() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "huge/a.bin", 4000);
+        for i in 0..20 {
+            write_file(tmp.path(), &format!("huge/deep/{}/b.bin", i), 100);
+        }
+        write_file(tmp.path(), "tiny/c.txt", 1);
+        write_file(tmp.path(), "empty/.keep", 0);
+        write_file(tmp.path(), "loose.txt", 7);
+
+        for threads in [1, 3, 16] {
+            let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+            wrapper.set_options(ScanOptions {
+                threads: Some(threads),
+                ..ScanOptions::default()
+            });
+            let overview = wrapper.overview_parallel();
+            assert_eq!(overview, wrapper.overview());
+            assert_eq!(overview[0].1, 6000);
+            assert_eq!(overview.len(), 4);
+        }
+    }
+
+    #[test]
+    fn slowest_dirs_ranks_by_read_time() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "slow/a.txt", 1);
+        write_file(tmp.path(), "slower/b.txt", 1);
+        write_file(tmp.path(), "quick/c.txt", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        assert!(wrapper.slowest_dirs(10).is_empty());
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            time_reads: true,
+            read_timer: ReadTimer::new(|dir, _| {
+                let millis = match dir.file_name().unwrap().to_str().unwrap() {
+                    "slower" => 60,
+                    "slow" => 20,
+                    _ => 1,
+                };
+                Duration::from_millis(millis)
+            }),
+            ..ScanOptions::default()
+        });
+        wrapper.load_entry();
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.slowest_dirs(2),
+            vec![
+                (format!("{}/slower", root), Duration::from_millis(60)),
+                (format!("{}/slow", root), Duration::from_millis(20)),
+            ]
+        );
+        assert_eq!(wrapper.slowest_dirs(10).len(), 4);
+    }
+
+    #[test]
+    fn sorted_paths_by_natural_order() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "file10", 1);
+        write_file(tmp.path(), "file2", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.sorted_paths_by(SortOrder::Natural),
+            vec![
+                root.to_string(),
+                format!("{}/file2", root),
+                format!("{}/file10", root),
+            ]
+        );
+    }
+
+    #[test]
+    fn ensure_loaded_reads_each_directory_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "sub/a.txt", 1);
+        write_file(tmp.path(), "sub/deeper/b.txt", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        let root = wrapper.get_root_path().to_string();
+        let sub = format!("{}/sub", root);
+
+        assert!(!wrapper.is_loaded(&root));
+        assert!(wrapper.ensure_loaded(&root).0);
+        assert_eq!(wrapper.get_children(), vec![sub.clone()]);
+        // Only one level was read.
+        assert!(wrapper.get_entry(&format!("{}/a.txt", sub)).is_none());
+
+        // Files added after the first read only show up if it re-scans.
+        write_file(tmp.path(), "late.txt", 1);
+        assert!(!wrapper.ensure_loaded(&root).0);
+        assert_eq!(wrapper.get_children().len(), 1);
+
+        assert!(wrapper.ensure_loaded(&sub).0);
+        assert!(wrapper.get_entry(&format!("{}/a.txt", sub)).is_some());
+        assert!(!wrapper.is_loaded(&format!("{}/deeper", sub)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn size_with_block_size_rounds_each_file_up() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "empty", 0);
+        write_file(tmp.path(), "one", 1);
+        write_file(tmp.path(), "exact", 4096);
+        write_file(tmp.path(), "sub/over", 4097);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        assert_eq!(wrapper.size_with_block_size(4096), 4 * 4096);
+        assert_eq!(wrapper.size_with_block_size(65536), 3 * 65536);
+        assert_eq!(wrapper.size_with_block_size(1), 8194);
+        assert_eq!(wrapper.size_with_block_size(0), 8194);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dedup_child_sizes_count_hardlinks_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let original = write_file(tmp.path(), "linked/a.bin", 100);
+        fs::hard_link(&original, tmp.path().join("linked/b.bin")).unwrap();
+        write_file(tmp.path(), "linked/c.bin", 10);
+        write_file(tmp.path(), "plain.txt", 5);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path();
+        let linked = format!("{}/linked", root);
+
+        assert_eq!(
+            wrapper.child_sizes(),
+            vec![(linked.clone(), 210), (format!("{}/plain.txt", root), 5)]
+        );
+        assert_eq!(
+            wrapper.child_sizes_dedup(),
+            vec![(linked.clone(), 110), (format!("{}/plain.txt", root), 5)]
+        );
+        assert_eq!(
+            wrapper.hardlinked_files(),
+            vec![format!("{}/a.bin", linked), format!("{}/b.bin", linked)]
+        );
+    }
+
+    #[test]
+    fn size_overview_accounts_sparse_files_and_hardlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let sparse = fs::File::create(tmp.path().join("sparse")).unwrap();
+        sparse.set_len(10 * 1024 * 1024).unwrap();
+        let linked = write_file(tmp.path(), "linked", 8192);
+        fs::hard_link(&linked, tmp.path().join("link")).unwrap();
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let overview = wrapper.size_overview();
+        assert_eq!(overview.apparent, 10 * 1024 * 1024 + 2 * 8192);
+        assert_eq!(overview.dedup_apparent, 10 * 1024 * 1024 + 8192);
+        assert!(overview.allocated < overview.apparent);
+    }
+
+    #[test]
+    fn find_stops_at_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..10 {
+            write_file(tmp.path(), &format!("dir{}/log{}.txt", i % 3, i), 1);
+        }
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let matcher = Glob::new("log*").unwrap();
+        assert_eq!(wrapper.find(&matcher, None).len(), 10);
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.find(&matcher, Some(4)),
+            vec![
+                format!("{}/dir0/log0.txt", root),
+                format!("{}/dir0/log3.txt", root),
+                format!("{}/dir0/log6.txt", root),
+                format!("{}/dir0/log9.txt", root),
+            ]
+        );
+        assert!(wrapper.find(&matcher, Some(0)).is_empty());
+    }
+
+    #[test]
+    fn largest_files_and_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a/big.bin", 300);
+        write_file(tmp.path(), "a/b/mid.bin", 200);
+        write_file(tmp.path(), "c/small.bin", 100);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.largest_files(Some(2)),
+            vec![
+                (format!("{}/a/big.bin", root), 300),
+                (format!("{}/a/b/mid.bin", root), 200)
+            ]
+        );
+        assert_eq!(
+            wrapper.largest_dirs(None),
+            vec![
+                (root.to_string(), 600),
+                (format!("{}/a", root), 500),
+                (format!("{}/a/b", root), 200),
+                (format!("{}/c", root), 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn visitor_sees_every_entry() {
+        use std::sync::atomic::AtomicU64;
+
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/b.txt", 20);
+        write_file(tmp.path(), "sub/deeper/c.txt", 30);
+
+        let size = AtomicU64::new(0);
+        let count = AtomicUsize::new(0);
+        let errors = EntryWrapper::visit(tmp.path(), |entry| {
+            size.fetch_add(entry.get_size(), Ordering::Relaxed);
+            count.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        assert!(errors.is_empty());
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        assert_eq!(size.into_inner(), wrapper.calculate_size());
+        assert_eq!(count.into_inner(), wrapper.count_entries() + 1);
+    }
+
+    #[test]
+    fn overview_sizes_first_level_without_storage() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "small/a", 10);
+        write_file(tmp.path(), "large/a", 100);
+        write_file(tmp.path(), "large/deep/b", 200);
+        write_file(tmp.path(), "medium/a", 50);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        let root = wrapper.get_root_path();
+
+        assert_eq!(
+            wrapper.overview(),
+            vec![
+                (format!("{}/large", root), 300),
+                (format!("{}/medium", root), 50),
+                (format!("{}/small", root), 10)
+            ]
+        );
+        assert_eq!(wrapper.sorted_paths(), vec![root.to_string()]);
+    }
+
+    #[test]
+    fn retain_subtree_drops_everything_else() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "keep/a.txt", 1);
+        write_file(tmp.path(), "keep/sub/b.txt", 2);
+        write_file(tmp.path(), "keeper/c.txt", 4);
+        write_file(tmp.path(), "drop/d.txt", 8);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let keep = format!("{}/keep", wrapper.get_root_path());
+
+        wrapper.retain_subtree(&keep).unwrap();
+        assert_eq!(wrapper.get_root_path(), keep);
+        assert_eq!(wrapper.count_entries(), 3);
+        assert_eq!(wrapper.calculate_size(), 3);
+        assert_eq!(wrapper.sorted_paths().len(), 4);
+        assert!(wrapper.retain_subtree("/does/not/exist").is_err());
+    }
+
+    #[test]
+    fn read_only_refuses_to_drop_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "keep/a.txt", 1);
+        write_file(tmp.path(), "drop/b.txt", 2);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path().to_string();
+        let before = wrapper.sorted_paths();
+
+        wrapper.set_read_only(true);
+        assert!(wrapper.retain_subtree(&format!("{}/keep", root)).is_err());
+        assert_eq!(wrapper.get_root_path(), root);
+        assert_eq!(wrapper.sorted_paths(), before);
+        assert!(tmp.path().join("drop/b.txt").exists());
+
+        wrapper.set_read_only(false);
+        wrapper.retain_subtree(&format!("{}/keep", root)).unwrap();
+        assert_eq!(wrapper.count_entries(), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn find_case_collisions_groups_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "Foo.txt", 1);
+        write_file(tmp.path(), "foo.txt", 1);
+        write_file(tmp.path(), "bar.txt", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.find_case_collisions(),
+            vec![vec![
+                format!("{}/Foo.txt", root),
+                format!("{}/foo.txt", root)
+            ]]
+        );
+    }
+
+    #[test]
+    fn last_scan_thread_count_follows_options() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "sub/a.txt", 1);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        assert_eq!(wrapper.last_scan_thread_count(), None);
+
+        wrapper.set_options(ScanOptions {
+            threads: Some(3),
+            ..ScanOptions::default()
+        });
+        assert!(wrapper.load_entry().is_empty());
+        assert_eq!(wrapper.last_scan_thread_count(), Some(3));
+        assert_eq!(wrapper.count_entries(), 2);
+    }
+
+    #[test]
+    fn scan_without_times() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/b.txt", 20);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            collect_times: false,
+            ..ScanOptions::default()
+        });
+        assert!(wrapper.load_entry().is_empty());
+        assert_eq!(wrapper.calculate_size(), 30);
+
+        let file = wrapper
+            .get_entry(&format!("{}/sub/b.txt", wrapper.get_root_path()))
+            .unwrap();
+        assert_eq!(file.get_accessed(), None);
+        assert_eq!(file.get_modified(), None);
+        assert_eq!(file.get_created(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_are_attributed_to_their_owner() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/b.txt", 20);
+        let uid = fs::metadata(tmp.path()).unwrap().uid();
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.find_by_owner(uid),
+            vec![
+                (format!("{}/sub/b.txt", root), 20),
+                (format!("{}/a.txt", root), 10)
+            ]
+        );
+        assert!(wrapper.find_by_owner(uid.wrapping_add(1)).is_empty());
+        assert_eq!(wrapper.size_by_owner(), HashMap::from([(uid, 30)]));
+    }
+
+    #[test]
+    fn contains_path_canonicalizes_queries() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "sub/a.txt", 1);
+        let other = tempfile::tempdir().unwrap();
+        write_file(other.path(), "b.txt", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        assert!(wrapper.contains_path(tmp.path().join("sub/a.txt")));
+        assert!(wrapper.contains_path(tmp.path().join("sub/../sub/a.txt")));
+        assert!(wrapper.contains_path(tmp.path()));
+        assert!(!wrapper.contains_path(other.path().join("b.txt")));
+        assert!(!wrapper.contains_path(tmp.path().join("missing")));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(other.path().join("b.txt"), tmp.path().join("out")).unwrap();
+            std::os::unix::fs::symlink("sub/a.txt", tmp.path().join("in")).unwrap();
+            std::os::unix::fs::symlink("sub", tmp.path().join("dir")).unwrap();
+            let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+            wrapper.load_entry();
+
+            // Links are their own entries, whatever they point to.
+            assert!(wrapper.contains_path(tmp.path().join("out")));
+            assert!(wrapper.contains_path(tmp.path().join("in")));
+            assert!(wrapper.contains_path(tmp.path().join("dir")));
+            // Below a link the path resolves through it.
+            assert!(wrapper.contains_path(tmp.path().join("dir/a.txt")));
+            assert!(wrapper.contains_path(tmp.path().join("sub/..")));
+        }
+    }
+
+    #[test]
+    fn size_changes_are_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "growing.log", 20);
+
+        #[cfg(unix)]
+        {
+            write_file(tmp.path(), "a/big.bin", 1000);
+            std::os::unix::fs::symlink("a/big.bin", tmp.path().join("link")).unwrap();
+            std::os::unix::fs::symlink("..", tmp.path().join("a/up")).unwrap();
+        }
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        // Symlinks are re-read as links, not as what they point to.
+        assert!(wrapper.find_size_changes(1).is_empty());
+
+        let changes = wrapper.find_size_changes_with(1, |path| {
+            let size = fs::symlink_metadata(path)?.len();
+            Ok(if path.ends_with("growing.log") {
+                size + 5
+            } else {
+                size
+            })
+        });
+        assert_eq!(
+            changes,
+            vec![SizeChange {
+                path: format!("{}/growing.log", wrapper.get_root_path()),
+                scanned: 20,
+                current: 25,
+            }]
+        );
+    }
+
+    #[test]
+    fn verify_sizes_option_checks_after_scan() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            verify_sizes_every: Some(1),
+            ..ScanOptions::default()
+        });
+        assert!(wrapper.load_entry().is_empty());
+    }
+
+    #[test]
+    fn group_by_parent_counts_direct_children() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "b.txt", 2);
+        write_file(tmp.path(), "sub/c.txt", 4);
+        write_file(tmp.path(), "sub/d.txt", 8);
+        write_file(tmp.path(), "sub/e.txt", 16);
+        make_dir(tmp.path(), "sub/empty");
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        let groups = wrapper.group_by_parent();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[root], (3, 3));
+        assert_eq!(groups[&format!("{}/sub", root)], (4, 28));
+        assert_eq!(groups[&format!("{}/sub/empty", root)], (0, 0));
+    }
+
+    #[test]
+    fn entries_under_prefix() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a/1.txt", 1);
+        write_file(tmp.path(), "a/b/2.txt", 1);
+        write_file(tmp.path(), "ab/3.txt", 1);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path().to_string();
+        let a = format!("{}/a", root);
+        let expected = vec![
+            format!("{}/1.txt", a),
+            format!("{}/b", a),
+            format!("{}/b/2.txt", a),
+        ];
+
+        let linear = wrapper.entries_under(&a);
+        wrapper.build_index();
+        assert_eq!(wrapper.entries_under(&a), expected);
+        assert_eq!(linear, expected);
+
+        wrapper.retain_subtree(&format!("{}/b", a)).unwrap();
+        assert!(wrapper.entries_under(&format!("{}/ab", root)).is_empty());
+        assert_eq!(wrapper.entries_under(&a), expected[1..].to_vec());
+    }
+
+    #[test]
+    fn scan_low_priority_loads_everything() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/b.txt", 20);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        assert!(wrapper.scan_low_priority().is_empty());
+        assert!(!wrapper.get_options().low_priority);
+        assert_eq!(wrapper.count_entries(), 3);
+        assert_eq!(wrapper.calculate_size(), 30);
+    }
+
+    #[test]
+    fn size_of_extension_matches_histogram() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.iso", 100);
+        write_file(tmp.path(), "sub/b.ISO", 200);
+        write_file(tmp.path(), "c.txt", 5);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let histogram = wrapper.size_by_extension();
+        assert_eq!(wrapper.size_of_extension("iso"), (300, 2));
+        assert_eq!(wrapper.size_of_extension(".ISO"), histogram["iso"]);
+        assert_eq!(wrapper.size_of_extension("txt"), histogram["txt"]);
+        assert_eq!(wrapper.size_of_extension("mp4"), (0, 0));
+    }
+
+    #[test]
+    fn size_exceeds_stops_early() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..4 {
+            write_file(tmp.path(), &format!("{}/big.bin", i), 100);
+            for j in 0..30 {
+                write_file(tmp.path(), &format!("{}/deep/x/{}.bin", i, j), 1);
+            }
+        }
+        // 4 dirs holding a big file and a deep dir with 30 small files each.
+        let all = 4 * 35;
+
+        // A single worker takes entries in breadth-first order, so every big
+        // file is seen before any `x` directory is read and none of the small
+        // files can be reached, whatever order the directories list in.
+        let visited = AtomicUsize::new(0);
+        let options = ScanOptions {
+            threads: Some(1),
+            ..ScanOptions::default()
+        };
+        assert!(size_exceeds_with(tmp.path(), 50, &options, |_| {
+            visited.fetch_add(1, Ordering::Relaxed);
+        }));
+        let visited = visited.into_inner();
+        assert!(visited < all / 4, "visited {} of {}", visited, all);
+
+        assert!(EntryWrapper::size_exceeds(tmp.path(), 519));
+        assert!(!EntryWrapper::size_exceeds(tmp.path(), 520));
+        assert!(!EntryWrapper::size_exceeds(tmp.path().join("missing"), 0));
+    }
+
+    #[test]
+    fn size_tiers_split_at_thresholds() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a", 9);
+        write_file(tmp.path(), "b", 10);
+        write_file(tmp.path(), "sub/c", 99);
+        write_file(tmp.path(), "sub/d", 100);
+        write_file(tmp.path(), "e", 500);
+        write_file(tmp.path(), "empty", 0);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path();
+        let paths = |names: &[&str]| -> Vec<String> {
+            names
+                .iter()
+                .map(|name| format!("{}/{}", root, name))
+                .collect()
+        };
+
+        assert_eq!(
+            wrapper.size_tiers_with(10, 100),
+            SizeTiers {
+                big: paths(&["e", "sub/d"]),
+                medium: paths(&["b", "sub/c"]),
+                small: paths(&["a", "empty"]),
+            }
+        );
+        assert_eq!(wrapper.size_tiers().small.len(), 6);
+    }
+
+    #[test]
+    fn size_by_category_groups_extensions() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "clip.mp4", 100);
+        write_file(tmp.path(), "song.MP3", 50);
+        write_file(tmp.path(), "src/main.rs", 10);
+        write_file(tmp.path(), "backup.zip", 70);
+        write_file(tmp.path(), "Makefile", 3);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        let category = |rel: &str| match wrapper.get_entry(&format!("{}/{}", root, rel)) {
+            Some(Entry::File(file)) => file.category(),
+            other => panic!("expected a file, got {:?}", other),
+        };
+        assert_eq!(category("clip.mp4"), FileCategory::Media);
+        assert_eq!(category("src/main.rs"), FileCategory::Code);
+        assert_eq!(category("backup.zip"), FileCategory::Archive);
+
+        let by_category = wrapper.size_by_category();
+        assert_eq!(by_category[&FileCategory::Media], (150, 2));
+        assert_eq!(by_category[&FileCategory::Code], (10, 1));
+        assert_eq!(by_category[&FileCategory::Archive], (70, 1));
+        assert_eq!(by_category[&FileCategory::Other], (3, 1));
+        assert!(!by_category.contains_key(&FileCategory::Document));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_errors_only_reports_unreadable_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "ok/a.txt", 1);
+        write_file(tmp.path(), "ok/b.txt", 1);
+        make_dir(tmp.path(), &format!("ok/{}", DENIED_PREFIX));
+
+        let errors = EntryWrapper::collect_errors_only(tmp.path());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains(DENIED_PREFIX));
+
+        let missing = EntryWrapper::collect_errors_only(tmp.path().join("missing"));
+        assert_eq!(missing.len(), 1);
+    }
+
+    #[test]
+    fn byte_budget_stops_scan_early() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..10 {
+            write_file(tmp.path(), &format!("d{}/a.bin", i), 100);
+            write_file(tmp.path(), &format!("d{}/sub/b.bin", i), 100);
+            write_file(tmp.path(), &format!("d{}/sub/deeper/c.bin", i), 100);
+        }
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        assert!(!wrapper.is_truncated());
+        assert_eq!(wrapper.calculate_size(), 3000);
+
+        let mut budgeted = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        budgeted.set_options(ScanOptions {
+            threads: Some(1),
+            max_bytes: Some(150),
+            ..ScanOptions::default()
+        });
+        budgeted.load_entry();
+        assert!(budgeted.is_truncated());
+        assert!(budgeted.calculate_size() < 3000);
+        assert!(budgeted.count_entries() < wrapper.count_entries());
+
+        // A later scan without a budget clears the flag.
+        budgeted.set_options(ScanOptions::default());
+        budgeted.load_entry();
+        assert!(!budgeted.is_truncated());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn space_reclaimed_counts_removed_bytes() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "keep.txt", 5);
+        write_file(tmp.path(), "old.log", 40);
+        write_file(tmp.path(), "cache/a.bin", 100);
+        write_file(tmp.path(), "cache/nested/b.bin", 200);
+
+        let before = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        before.load_entry();
+
+        fs::remove_file(tmp.path().join("old.log")).unwrap();
+        fs::remove_dir_all(tmp.path().join("cache")).unwrap();
+
+        let after = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        after.load_entry();
+
+        let root = before.get_root_path();
+        assert_eq!(EntryWrapper::space_reclaimed(&before, &after), -340);
+        assert_eq!(EntryWrapper::space_reclaimed(&after, &before), 340);
+        assert_eq!(
+            EntryWrapper::largest_removed(&before, &after, None),
+            vec![
+                (format!("{}/cache", root), 300),
+                (format!("{}/old.log", root), 40),
+            ]
+        );
+        assert!(EntryWrapper::largest_removed(&after, &before, None).is_empty());
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        let digest = |bytes: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.write(bytes);
+            hash.finish()
+        };
+        assert_eq!(digest(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(digest(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(digest(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn structure_hash_detects_touched_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 5);
+        write_file(tmp.path(), "sub/b.txt", 10);
+
+        let scan = || {
+            let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+            wrapper.load_entry();
+            wrapper
+        };
+        let first = scan();
+        let hash = first.structure_hash();
+        assert_eq!(first.structure_hash(), hash);
+        assert_eq!(scan().structure_hash(), hash);
+
+        let touched = fs::File::options()
+            .write(true)
+            .open(tmp.path().join("sub/b.txt"))
+            .unwrap();
+        touched
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let second = scan();
+        assert_ne!(second.structure_hash(), hash);
+
+        // The cache follows storage.
+        first.load_entry();
+        assert_eq!(first.structure_hash(), second.structure_hash());
+    }
+
+    #[test]
+    fn diff_reports_moves_by_inode() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a/moved.txt", 10);
+        write_file(tmp.path(), "a/gone.txt", 10);
+        make_dir(tmp.path(), "b");
+
+        let before = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        before.load_entry();
+
+        fs::rename(
+            tmp.path().join("a/moved.txt"),
+            tmp.path().join("b/moved.txt"),
+        )
+        .unwrap();
+        fs::remove_file(tmp.path().join("a/gone.txt")).unwrap();
+        write_file(tmp.path(), "b/new.txt", 10);
+
+        let after = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        after.load_entry();
+
+        let root = before.get_root_path();
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                TreeChange::Moved {
+                    from: format!("{}/a/moved.txt", root),
+                    to: format!("{}/b/moved.txt", root),
+                },
+                TreeChange::Added(format!("{}/b/new.txt", root)),
+                TreeChange::Removed(format!("{}/a/gone.txt", root)),
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn dirs_with_large_files_finds_single_big_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            write_file(tmp.path(), &format!("many/f{}", i), 10);
+        }
+        write_file(tmp.path(), "mixed/small.txt", 10);
+        write_file(tmp.path(), "mixed/nested/huge.bin", 1000);
+        make_dir(tmp.path(), "empty");
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path().to_string();
+        let mixed = format!("{}/mixed", root);
+        assert_eq!(
+            wrapper.dirs_with_large_files(500),
+            vec![root.clone(), mixed.clone(), format!("{}/nested", mixed)]
+        );
+        assert_eq!(
+            wrapper.max_child_file_size(&format!("{}/many", root)),
+            Some(10)
+        );
+        assert_eq!(
+            wrapper.max_child_file_size(&format!("{}/empty", root)),
+            Some(0)
+        );
+        assert_eq!(
+            wrapper.max_child_file_size(&format!("{}/many/f0", root)),
+            None
+        );
+
+        // The cached maxima follow storage changes.
+        wrapper.retain_subtree(&format!("{}/many", root)).unwrap();
+        assert!(wrapper.dirs_with_large_files(500).is_empty());
+    }
+
+    #[test]
+    fn estimate_size_is_close_on_uniform_tree() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..400 {
+            for j in 0..5 {
+                write_file(tmp.path(), &format!("d{}/f{}", i, j), 100);
+            }
+        }
+        let exact = 400 * 5 * 100;
+
+        let (full, errors) = EntryWrapper::estimate_size(tmp.path(), 1.0).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(full.estimate, exact);
+        assert_eq!(full.entries, 400 + 400 * 5);
+        assert_eq!(full.sampled_fraction, 1.0);
+
+        let (approx, _) = EntryWrapper::estimate_size(tmp.path(), 0.5).unwrap();
+        let error = (approx.estimate as f64 - exact as f64).abs() / exact as f64;
+        // Standard error is about 5% at this width.
+        assert!(error < 0.25, "estimate {} for {}", approx.estimate, exact);
+        assert!(approx.sampled_fraction < 0.75);
+    }
+
+    #[test]
+    fn flat_listing_has_one_row_per_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "sub/b.txt", 2);
+        write_file(tmp.path(), "sub/deeper/c.txt", 4);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        let rows = wrapper.flat_listing();
+        assert_eq!(rows.len(), wrapper.count_entries());
+        assert_eq!(rows[0], (format!("{}/a.txt", root), 1, false));
+        assert!(rows.contains(&(format!("{}/sub", root), 6, true)));
+        assert!(rows.contains(&(format!("{}/sub/deeper", root), 4, true)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dedup_roots_drops_symlinked_duplicates() {
+        let tmp = tempfile::tempdir().unwrap();
+        make_dir(tmp.path(), "real");
+        make_dir(tmp.path(), "other");
+        let real = tmp.path().join("real");
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let other = tmp.path().join("other");
+        let missing = tmp.path().join("missing");
+
+        let (roots, dropped) =
+            EntryWrapper::dedup_roots(&[&real, &link, &other, &missing, &real.join(".")]);
+        assert_eq!(roots, vec![real.clone(), other, missing]);
+        assert_eq!(dropped, vec![(link, real.clone()), (real.join("."), real)]);
+    }
+
+    #[test]
+    fn walk_events_report_every_entry() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "sub/b.txt", 2);
+        write_file(tmp.path(), "sub/deeper/c.txt", 3);
+        write_file(tmp.path(), "sub/deeper/d.txt", 4);
+
+        let events: Vec<WalkEvent> = EntryWrapper::walk_events(tmp.path()).iter().collect();
+        let count = |matches: fn(&WalkEvent) -> bool| events.iter().filter(|e| matches(e)).count();
+        assert_eq!(count(|e| matches!(e, WalkEvent::FoundFile { .. })), 4);
+        assert_eq!(count(|e| matches!(e, WalkEvent::EnteredDir { .. })), 3);
+        assert_eq!(count(|e| matches!(e, WalkEvent::FinishedDir { .. })), 3);
+        assert_eq!(count(|e| matches!(e, WalkEvent::Error(_))), 0);
+
+        let sizes: u64 = events
+            .iter()
+            .map(|e| match e {
+                WalkEvent::FoundFile { size, .. } => *size,
+                _ => 0,
+            })
+            .sum();
+        assert_eq!(sizes, 10);
+        match &events[0] {
+            WalkEvent::EnteredDir { depth, .. } => assert_eq!(*depth, 0),
+            other => panic!("walk started with {:?}", other),
+        }
+
+        let missing: Vec<WalkEvent> = EntryWrapper::walk_events(tmp.path().join("missing"))
+            .iter()
+            .collect();
+        assert!(matches!(missing[..], [WalkEvent::Error(_)]));
+    }
+
+    #[test]
+    fn inode_usage_ranks_many_small_files_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..50 {
+            write_file(tmp.path(), &format!("cache/{}", i), 1);
+        }
+        write_file(tmp.path(), "videos/movie.mkv", 10_000);
+        write_file(tmp.path(), "videos/extra/trailer.mkv", 5_000);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.inode_usage_by_dir(),
+            vec![
+                (root.to_string(), 55),
+                (format!("{}/cache", root), 50),
+                (format!("{}/videos", root), 3),
+                (format!("{}/videos/extra", root), 1),
+            ]
+        );
+        assert_eq!(
+            wrapper.largest_dirs(Some(2))[1].0,
+            format!("{}/videos", root)
+        );
+    }
+
+    #[test]
+    fn dirs_only_json_round_trips_without_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "sub/b.txt", 2);
+        write_file(tmp.path(), "sub/deeper/c.txt", 4);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let mut json = vec![];
+        wrapper.to_json_dirs_only(&mut json).unwrap();
+        let skeleton = DirSkeleton::from_json(json.as_slice()).unwrap();
+
+        let root = wrapper.get_root_path();
+        let sub = format!("{}/sub", root);
+        assert_eq!(skeleton.root, root);
+        assert_eq!(skeleton.dirs.len(), 3);
+        assert!(skeleton.get(&format!("{}/a.txt", root)).is_none());
+        assert_eq!(skeleton.get(root).unwrap().size, 7);
+        assert_eq!(skeleton.get(root).unwrap().entries, 5);
+        assert_eq!(skeleton.get(&sub).unwrap().size, 6);
+        assert_eq!(skeleton.get(&sub).unwrap().depth, 1);
+        assert_eq!(skeleton.children(root), vec![skeleton.get(&sub).unwrap()]);
+        for (path, size) in wrapper.largest_dirs(None) {
+            assert_eq!(skeleton.get(&path).unwrap().size, size);
+        }
+    }
+
+    #[test]
+    fn size_where_sums_matching_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "app.log", 2_000_000);
+        write_file(tmp.path(), "old/app.1.LOG", 3_000_000);
+        write_file(tmp.path(), "old/debug.log", 500);
+        write_file(tmp.path(), "video.mkv", 9_000_000);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let big_logs = Extensions::new(["log"]).and(MinSize(1_000_000));
+        assert_eq!(wrapper.size_where(&big_logs), 5_000_000);
+        assert_eq!(wrapper.size_where(&Extensions::new(["log"])), 5_000_500);
+        assert_eq!(
+            wrapper.size_where(&Glob::new("*").unwrap()),
+            wrapper.calculate_size()
+        );
+    }
+
+    #[test]
+    fn topology_matches_stored_children() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+        write_file(tmp.path(), "sub/b.txt", 2);
+        write_file(tmp.path(), "sub/deeper/c.txt", 3);
+        make_dir(tmp.path(), "empty");
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let mut json = vec![];
+        wrapper.topology().to_json(&mut json).unwrap();
+        let topology = TopologyIndex::from_json(json.as_slice()).unwrap();
+        assert_eq!(topology, wrapper.topology());
+
+        let mut dirs = 0;
+        for entry in wrapper.storage.values() {
+            let path = entry.get_format_path();
+            assert_eq!(topology.parent_of(&path), entry.get_parent().as_deref());
+            match entry {
+                Entry::Dir(dir) => {
+                    dirs += 1;
+                    assert_eq!(topology.children_of(&path), Some(dir.get_children()));
+                }
+                Entry::File(_) => assert_eq!(topology.children_of(&path), None),
+            }
+        }
+        assert_eq!(dirs, 4);
+        assert_eq!(topology.parent_of(wrapper.get_root_path()), None);
+    }
+
+    #[cfg(feature = "compressed-storage")]
+    #[test]
+    fn compressed_storage_round_trips_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            write_file(
+                tmp.path(),
+                &format!("tree/project{}/src/module{}/file{}.rs", i % 4, i % 20, i),
+                i,
+            );
+        }
+        let tree = tmp.path().join("tree");
+
+        let memory = EntryWrapper::new_with_memstorage(&tree).unwrap();
+        memory.load_entry();
+        let compressed = EntryWrapper::new_with_compressed_storage(&tree).unwrap();
+        assert!(compressed.load_entry().is_empty());
+        assert_eq!(compressed.sorted_paths(), memory.sorted_paths());
+        assert_eq!(compressed.calculate_size(), memory.calculate_size());
+
+        // Entries read back exactly as they were stored, and take less room.
+        let storage = CompressedMemStorage::new();
+        let mut plain_bytes = 0;
+        for entry in memory.storage.values() {
+            let path = entry.get_format_path();
+            plain_bytes += std::mem::size_of::<Entry>() + 2 * path.len() + entry.get_name().len();
+            storage.set(path.clone(), entry.clone());
+            assert_eq!(
+                serde_json::to_value(storage.get(&path).unwrap()).unwrap(),
+                serde_json::to_value(&entry).unwrap()
+            );
+        }
+        // A lower bound for plain storage, allocator overhead not counted.
+        assert!(storage.stored_bytes() * 2 < plain_bytes);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_scan_matches_memory_scan() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "tree/a.txt", 10);
+        write_file(tmp.path(), "tree/sub/b.txt", 20);
+        write_file(tmp.path(), "tree/sub/deeper/c.txt", 30);
+        let tree = tmp.path().join("tree");
+
+        let memory = EntryWrapper::new_with_memstorage(&tree).unwrap();
+        memory.load_entry();
+        let mut sqlite = EntryWrapper::new_with_sqlite(&tree, tmp.path().join("scan.db")).unwrap();
+        sqlite.set_options(ScanOptions {
+            threads: Some(4),
+            ..ScanOptions::default()
+        });
+        assert!(sqlite.load_entry().is_empty());
+
+        assert_eq!(sqlite.count_entries(), memory.count_entries());
+        assert_eq!(sqlite.calculate_size(), 60);
+        assert_eq!(sqlite.get_children().len(), 2);
+    }
+
+    #[test]
+    fn mostly_denied_scan_reports_insufficient_permissions() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..3 {
+            write_file(tmp.path(), &format!("{}{}/a.txt", DENIED_PREFIX, i), 1);
+        }
+        write_file(tmp.path(), "open/b.txt", 2);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        let errors = wrapper.load_entry();
+
+        // Three of the five directories, root included, were denied.
+        assert_eq!(errors.len(), 4);
+        let elevated: Vec<_> = errors
+            .iter()
+            .filter_map(|e| e.downcast_ref::<DrfsError>())
+            .collect();
+        assert_eq!(
+            elevated,
+            vec![&DrfsError::InsufficientPermissions { error_ratio: 0.6 }]
+        );
+        // What could be read is still there.
+        assert_eq!(wrapper.calculate_size(), 2);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            permission_error_ratio: Some(0.75),
+            ..ScanOptions::default()
+        });
+        assert_eq!(wrapper.load_entry().len(), 3);
+    }
+
+    #[test]
+    fn saved_scan_loads_without_the_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "tree/a.txt", 10);
+        write_file(tmp.path(), "tree/sub/b.txt", 20);
+        let tree = tmp.path().join("tree");
+        let saved = tmp.path().join("scan.json");
+
+        let wrapper = EntryWrapper::new_with_memstorage(&tree).unwrap();
+        wrapper.load_entry();
+        wrapper.save(&saved).unwrap();
+        fs::remove_dir_all(&tree).unwrap();
+
+        let loaded = EntryWrapper::load(&saved).unwrap();
+        assert_eq!(loaded.get_root_path(), wrapper.get_root_path());
+        assert_eq!(loaded.sorted_paths(), wrapper.sorted_paths());
+        assert_eq!(loaded.calculate_size(), 30);
+        assert_eq!(loaded.count_entries(), 3);
+        let sub = format!("{}/sub", loaded.get_root_path());
+        // Nothing is read again from the now missing files.
+        let (read, errors) = loaded.ensure_loaded(&sub);
+        assert!(!read && errors.is_empty());
+        match loaded.get_entry(&sub) {
+            Some(Entry::Dir(dir)) => assert_eq!(dir.get_children().len(), 1),
+            other => panic!("expected a directory, got {:?}", other),
+        }
+
+        fs::write(&saved, "{}").unwrap();
+        assert!(EntryWrapper::load(&saved).is_err());
+    }
+
+    #[test]
+    fn detailed_children_match_the_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "file10.txt", 5);
+        write_file(tmp.path(), "file9.txt", 40);
+        write_file(tmp.path(), "sub/a.bin", 20);
+        write_file(tmp.path(), "sub/deeper/b.bin", 30);
+        make_dir(tmp.path(), "empty");
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path();
+        let child = |name: &str, is_dir, size| ChildInfo {
+            path: format!("{}/{}", root, name),
+            name: name.to_string(),
+            is_dir,
+            size,
+        };
+
+        let by_size = wrapper.get_children_detailed(ChildKey::Size);
+        assert_eq!(
+            by_size,
+            vec![
+                child("sub", true, 50),
+                child("file9.txt", false, 40),
+                child("file10.txt", false, 5),
+                child("empty", true, 0),
+            ]
+        );
+        let mut paths: Vec<_> = by_size.into_iter().map(|child| child.path).collect();
+        let mut expected = wrapper.get_children();
+        paths.sort();
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        let names: Vec<_> = wrapper
+            .get_children_detailed(ChildKey::Name(SortOrder::Natural))
+            .into_iter()
+            .map(|child| child.name)
+            .collect();
+        assert_eq!(names, vec!["empty", "file9.txt", "file10.txt", "sub"]);
+    }
+
+    #[test]
+    fn extension_sizes_in_parallel_match_sequential() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..40 {
+            let ext = ["mkv", "MP4", "txt", "mkv.part"][i % 4];
+            write_file(tmp.path(), &format!("d{}/f{}.{}", i % 5, i, ext), i + 1);
+        }
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            threads: Some(3),
+            ..ScanOptions::default()
+        });
+        wrapper.load_entry();
+
+        let sizes = wrapper
+            .size_of_extensions_parallel(&["mkv", ".mp4", "flac"])
+            .unwrap();
+        assert_eq!(sizes.len(), 3);
+        assert_eq!(sizes["mkv"], wrapper.size_of_extension("mkv").0);
+        assert_eq!(sizes["mp4"], wrapper.size_of_extension("mp4").0);
+        assert!(sizes["mp4"] > 0);
+        assert_eq!(sizes["flac"], 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_symlinks_count_their_own_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "data/big.bin", 10_000);
+        std::os::unix::fs::symlink("data/big.bin", tmp.path().join("link.bin")).unwrap();
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        assert!(wrapper.load_entry().is_empty());
+
+        let link = format!("{}/link.bin", wrapper.get_root_path());
+        assert!(wrapper.get_entry(&link).unwrap().is_symlink());
+        assert_eq!(
+            wrapper.calculate_size(),
+            10_000 + "data/big.bin".len() as u64
+        );
+        assert_eq!(wrapper.largest_files(Some(1))[0].1, 10_000);
+        assert_eq!(wrapper.size_of_extension("bin").1, 2);
+    }
+
+    #[cfg(feature = "compressibility")]
+    #[test]
+    fn compressibility_tells_zeros_from_noise() {
+        let tmp = tempfile::tempdir().unwrap();
+        let zeros = tmp.path().join("zeros");
+        let noise = tmp.path().join("noise");
+        fs::create_dir_all(&zeros).unwrap();
+        fs::create_dir_all(&noise).unwrap();
+        fs::write(zeros.join("a.bin"), vec![0; 256 * 1024]).unwrap();
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let random: Vec<u8> = (0..256 * 1024)
+            .map(|_| {
+                // xorshift, good enough to defeat zstd.
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        fs::write(noise.join("a.bin"), random).unwrap();
+
+        let ratio = |root: &Path| {
+            let wrapper = EntryWrapper::new_with_memstorage(root).unwrap();
+            wrapper.load_entry();
+            wrapper.estimate_compressibility(1024 * 1024)
+        };
+        let zeros = ratio(&zeros);
+        let noise = ratio(&noise);
+        let both = ratio(tmp.path());
+        assert!(zeros < 0.01, "zeros: {}", zeros);
+        assert!(noise > 0.95, "noise: {}", noise);
+        assert!(zeros < both && both < noise, "both: {}", both);
+
+        let empty = tempfile::tempdir().unwrap();
+        assert_eq!(ratio(empty.path()), 1.0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permission_audit_sorts_risky_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let chmod = |name: &str, mode| {
+            let path = write_file(tmp.path(), name, 1);
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        };
+        chmod("shared.txt", 0o666);
+        chmod("bin/su", 0o4755);
+        chmod("bin/wall", 0o2755);
+        chmod("bin/both", 0o6777);
+        chmod("private.txt", 0o600);
+        chmod("frozen.txt", 0o444);
+        fs::set_permissions(tmp.path().join("bin"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("private.txt", tmp.path().join("link")).unwrap();
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path();
+        let paths = |names: &[&str]| -> Vec<String> {
+            names
+                .iter()
+                .map(|name| format!("{}/{}", root, name))
+                .collect()
+        };
+
+        let audit = wrapper.permission_audit();
+        assert_eq!(audit.world_writable, paths(&["bin/both", "shared.txt"]));
+        assert_eq!(
+            wrapper.find_by_mode(|mode| mode & 0o002 != 0),
+            audit.world_writable
+        );
+        assert_eq!(
+            wrapper.find_by_mode(|mode| mode & 0o777 == 0o600),
+            paths(&["private.txt"])
+        );
+        let shared = wrapper.get_entry(&paths(&["shared.txt"])[0]).unwrap();
+        assert!(shared.is_world_writable() && !shared.is_readonly());
+        assert!(wrapper
+            .get_entry(&paths(&["frozen.txt"])[0])
+            .unwrap()
+            .is_readonly());
+        assert!(!wrapper
+            .get_entry(&paths(&["link"])[0])
+            .unwrap()
+            .is_world_writable());
+        assert_eq!(audit.setuid, paths(&["bin/both", "bin/su"]));
+        assert_eq!(audit.setgid, paths(&["bin/both", "bin/wall"]));
+    }
+
+    #[test]
+    fn build_report_summarizes_the_scan() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a/big.BIN", 300);
+        write_file(tmp.path(), "a/b/mid.bin", 200);
+        write_file(tmp.path(), "c/notes.txt", 100);
+        write_file(tmp.path(), "README", 5);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        let errors = wrapper.load_entry();
+        let report = wrapper.build_report();
+
+        let root = wrapper.get_root_path();
+        assert_eq!(report.root, root);
+        assert_eq!(report.entries, wrapper.count_entries());
+        assert_eq!((report.files, report.dirs), (4, 3));
+        assert_eq!(report.sizes.apparent, 605);
+        assert_eq!(report.top_files[0], (format!("{}/a/big.BIN", root), 300));
+        assert_eq!(report.top_files.len(), 4);
+        assert_eq!(
+            report.extensions.into_iter().collect::<Vec<_>>(),
+            vec![("bin".to_string(), (500, 2)), ("txt".to_string(), (100, 1))]
+        );
+        assert_eq!(report.errors, errors.len());
+        assert_eq!(report.errors_truncated, 0);
+        assert!(!report.truncated);
+    }
+
+    #[test]
+    fn to_json_dumps_every_entry_by_key() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/b.txt", 20);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path().to_string();
+
+        let json: serde_json::Value = serde_json::from_str(&wrapper.to_json().unwrap()).unwrap();
+        assert_eq!(json["root"], root.as_str());
+        let entries = json["entries"].as_object().unwrap();
+        assert_eq!(entries.len(), 4);
+
+        let sub = &entries[&format!("{}/sub", root)];
+        assert_eq!(sub["type"], "dir");
+        assert_eq!(sub["parent"], root.as_str());
+        assert_eq!(
+            sub["children"],
+            serde_json::json!([format!("{}/sub/b.txt", root)])
+        );
+        let b = &entries[&format!("{}/sub/b.txt", root)];
+        assert_eq!(b["type"], "file");
+        assert_eq!(b["size"], 20);
+        assert!(b["modified"].is_string());
+
+        // Entries read back as they were written.
+        let entry: Entry = serde_json::from_value(b.clone()).unwrap();
+        let stored = wrapper.get_entry(&format!("{}/sub/b.txt", root)).unwrap();
+        assert_eq!(entry.get_size(), 20);
+        assert_eq!(entry.get_parent(), stored.get_parent());
+        assert_eq!(entry.get_modified(), stored.get_modified());
+        assert_eq!(serde_json::to_value(&entry).unwrap(), *b);
+    }
+
+    #[test]
+    fn entry_times_that_failed_are_null() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 1);
+
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            collect_times: false,
+            ..ScanOptions::default()
+        });
+        wrapper.load_entry();
+
+        let path = format!("{}/a.txt", wrapper.get_root_path());
+        let json = serde_json::to_value(wrapper.get_entry(&path).unwrap()).unwrap();
+        assert!(json["modified"].is_null());
+        let entry: Entry = serde_json::from_value(json).unwrap();
+        assert_eq!(entry.get_modified(), None);
+        assert_eq!(entry.get_time_errors().len(), 3);
+    }
+
+    #[test]
+    fn export_dot_draws_nodes_and_edges() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a.txt", 10);
+        write_file(tmp.path(), "sub/say \"hi\".txt", 20);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let mut dot = vec![];
+        wrapper.export_dot(&mut dot, 10).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        let root_name = tmp.path().canonicalize().unwrap();
+        let root_name = root_name.file_name().unwrap().to_string_lossy();
+
+        // Nodes are numbered in path order: root, a.txt, sub, sub/say "hi".txt.
+        let expected = format!(
+            "digraph drfs {{\n    \
+             n0 [shape=folder, label=\"{}\\n30 B\"];\n    \
+             n1 [shape=note, label=\"a.txt\\n10 B\"];\n    \
+             n2 [shape=folder, label=\"sub\\n20 B\"];\n    \
+             n3 [shape=note, label=\"say \\\"hi\\\".txt\\n20 B\"];\n    \
+             n0 -> n1;\n    \
+             n0 -> n2;\n    \
+             n2 -> n3;\n\
+             }}\n",
+            root_name
+        );
+        assert_eq!(dot, expected);
+
+        let mut refused = vec![];
+        assert!(wrapper.export_dot(&mut refused, 3).is_err());
+        assert!(refused.is_empty());
+    }
+
+    #[test]
+    fn tiny_files_collapse_into_one_node() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..50 {
+            write_file(tmp.path(), &format!("tiny/{}.txt", i), 3);
+        }
+        write_file(tmp.path(), "tiny/big.bin", 500);
+        write_file(tmp.path(), "lonely/small.txt", 1);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path().to_string();
+
+        let nodes = tree_nodes(wrapper.storage.values(), 100);
+        let aggregates: Vec<&TreeNode> = nodes
+            .iter()
+            .filter(|node| matches!(node.kind, TreeNodeKind::SmallFiles(_)))
+            .collect();
+        assert_eq!(aggregates.len(), 1);
+        assert_eq!(aggregates[0].kind, TreeNodeKind::SmallFiles(50));
+        assert_eq!(aggregates[0].size, 150);
+        assert_eq!(aggregates[0].parent, Some(format!("{}/tiny", root)));
+
+        // Root, two dirs, big.bin, the single small file and the aggregate.
+        assert_eq!(nodes.len(), 6);
+        assert!(nodes
+            .iter()
+            .any(|node| node.path == format!("{}/lonely/small.txt", root)));
+
+        let mut dot = vec![];
+        wrapper.export_dot_collapsed(&mut dot, 6, 100).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("n5 [shape=box3d, label=\"(many small files) (50 files)\\n150 B\"];"));
+        assert!(dot.contains("n3 -> n5;"));
+        assert!(wrapper.export_dot(&mut vec![], 6).is_err());
+    }
+
+    #[test]
+    fn top_heavy_dirs_reports_dominant_child() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "heavy/huge.bin", 900);
+        write_file(tmp.path(), "heavy/small.txt", 100);
+        write_file(tmp.path(), "even/a.txt", 50);
+        write_file(tmp.path(), "even/b.txt", 50);
+        write_file(tmp.path(), "other.txt", 1000);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+        let root = wrapper.get_root_path().to_string();
+
+        let heavy = wrapper.top_heavy_dirs(0.8);
+        assert_eq!(heavy.len(), 1);
+        let (dir, child, fraction) = &heavy[0];
+        assert_eq!(dir, &format!("{}/heavy", root));
+        assert_eq!(child, &format!("{}/heavy/huge.bin", root));
+        assert!((fraction - 0.9).abs() < 1e-9);
+
+        // The root splits into 1000 + 1000 + 100 out of 2100.
+        let heavy = wrapper.top_heavy_dirs(0.4);
+        assert_eq!(heavy.len(), 3);
+        assert_eq!(heavy[0].0, format!("{}/heavy", root));
+        assert_eq!(heavy[1].0, format!("{}/even", root));
+        assert_eq!(heavy[2].0, root);
+        assert_eq!(heavy[2].1, format!("{}/heavy", root));
+    }
+}
@@ -0,0 +1,27 @@
+use std::error::Error;
+use std::fmt;
+
+/// Errors `drfs` reports that callers may want to tell apart, found by
+/// downcasting a `GenericError`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrfsError {
+    /// So many directories couldn't be read for lack of permissions that the
+    /// results are of little use. `error_ratio` is the fraction of scanned
+    /// directories that were denied, see `ScanOptions::permission_error_ratio`.
+    InsufficientPermissions { error_ratio: f64 },
+}
+
+impl fmt::Display for DrfsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrfsError::InsufficientPermissions { error_ratio } => write!(
+                f,
+                "{:.0}% of directories could not be read for lack of permissions, \
+                 results are partial; try again with more privileges",
+                error_ratio * 100.0
+            ),
+        }
+    }
+}
+
+impl Error for DrfsError {}
@@ -1,3 +1,29 @@
+pub mod entry;
+pub mod error;
+pub mod matcher;
+pub mod priority;
+pub mod store;
+pub mod util;
+pub mod walk;
+
+#[cfg(test)]
+mod test_util;
+
+pub use entry::{
+    ApproxResult, ChildInfo, ChildKey, ContentKind, DirEntry, DirSkeleton, DirSummary, Entry,
+    EntryWrapper, FileCategory, FileEntry, PathDisplay, PermissionAudit, ScanOptions, ScanReport,
+    SizeOverview, SizeTiers, TopologyIndex, TreeChange,
+};
+pub use error::DrfsError;
+pub use matcher::Matcher;
+#[cfg(feature = "compressed-storage")]
+pub use store::compressedmemstorage::CompressedMemStorage;
+#[cfg(feature = "sqlite")]
+pub use store::sqlitestorage::SqliteStorage;
+pub use store::{memstorage::MemStorage, Storage};
+
+pub type GenericError = Box<dyn std::error::Error + Send + Sync>;
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,560 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+
+use drfs::entry::CONFIG_FILE;
+use drfs::matcher::{ExcludeSet, Glob};
+use drfs::util::{eta, format_eta, format_size, size_bar, SortOrder};
+use drfs::walk::ScanProgress;
+use drfs::{ContentKind, EntryWrapper, GenericError, PathDisplay, ScanOptions};
+
+const BAR_WIDTH: usize = 10;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Recursively scans a directory and reports its disk usage.
+#[derive(Parser)]
+#[command(name = "drfs")]
+struct Args {
+    /// Directories to scan, each reported on its own. Targets that are the
+    /// same directory as an earlier one, e.g. through a symlink, are skipped
+    #[arg(default_value = ".")]
+    targets: Vec<PathBuf>,
+
+    /// Print the N most deeply nested directories
+    #[arg(long, value_name = "N")]
+    deepest: Option<usize>,
+
+    /// Print entries whose name matches the glob PATTERN, or whose path
+    /// does if the pattern contains a separator
+    #[arg(long, value_name = "PATTERN")]
+    find: Option<String>,
+
+    /// Print the largest files
+    #[arg(long)]
+    largest_files: bool,
+
+    /// Print the largest directories by recursive size
+    #[arg(long)]
+    largest_dirs: bool,
+
+    /// Print the N directories that took the longest to read
+    #[arg(long, value_name = "N")]
+    slowest_dirs: Option<usize>,
+
+    /// Order of --find results: lexicographic, natural or natural-ci
+    #[arg(long, value_name = "ORDER", default_value = "lexicographic")]
+    sort: SortOrder,
+
+    /// Leave entries less than N levels below the target out of --find,
+    /// --largest-* and --deepest, they still count toward sizes
+    #[arg(long, value_name = "N")]
+    min_depth: Option<usize>,
+
+    /// Don't descend below N levels under the target, 0 scans only the
+    /// target itself
+    #[arg(short = 'd', long, value_name = "N")]
+    depth: Option<usize>,
+
+    /// Cap the number of results printed by --find and --largest-*
+    #[arg(long, value_name = "N")]
+    max_results: Option<usize>,
+
+    /// Number of worker threads, defaults to one per cpu up to 32
+    #[arg(short = 'j', long, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Skip reading timestamps, which speeds up scans that only need sizes,
+    /// =false reads them even if the config file turns them off
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    no_times: Option<bool>,
+
+    /// After scanning, re-check the size of every N-th file and warn if it changed
+    #[arg(long, value_name = "N")]
+    verify_sizes: Option<usize>,
+
+    /// Report at most N scan errors, only counting the rest
+    #[arg(long, value_name = "N")]
+    max_errors: Option<usize>,
+
+    /// Scan at lowered cpu and io priority (best effort, Linux only)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    nice: Option<bool>,
+
+    /// Report block devices by capacity (needs the device-size feature)
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    device_sizes: Option<bool>,
+
+    /// Most tasks an idle worker steals at once
+    #[arg(long, value_name = "N")]
+    steal_batch: Option<usize>,
+
+    /// Stop reading new directories once N bytes of files were found
+    #[arg(long, value_name = "N")]
+    max_bytes: Option<u64>,
+
+    /// Skip files whose content is of this kind, e.g. image (repeatable, slow)
+    #[arg(long, value_name = "KIND")]
+    skip_content: Vec<ContentKind>,
+
+    /// Count hardlinked files once in the size listing and mark the ones
+    /// listed directly
+    #[arg(long)]
+    dedup_display: bool,
+
+    /// Descend into symlinked directories instead of listing symlinks as
+    /// entries of their own
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    follow_symlinks: Option<bool>,
+
+    /// Show a line with the entries scanned so far and an estimated time
+    /// left on stderr while scanning
+    #[arg(long)]
+    progress: bool,
+
+    /// Mark directories that are mount points in the size listing
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    mounts: Option<bool>,
+
+    /// Measure how long each directory takes to read, always on with
+    /// --slowest-dirs
+    #[arg(
+        long,
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    time_reads: Option<bool>,
+
+    /// Fail the scan once more than this fraction of the directories, between
+    /// 0 and 1, couldn't be read for lack of permissions
+    #[arg(long, value_name = "RATIO", value_parser = parse_ratio)]
+    permission_error_ratio: Option<f64>,
+
+    /// Skip entries matching the glob PATTERN and everything below them,
+    /// e.g. node_modules, patterns with a / match the path below the target,
+    /// e.g. src/target (repeatable)
+    #[arg(short = 'x', long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Skip entries matching the glob patterns listed in FILE, one per line
+    /// (repeatable)
+    #[arg(long, value_name = "FILE")]
+    exclude_from: Vec<PathBuf>,
+
+    /// How to print paths: absolute, relative or basename
+    #[arg(long, value_name = "STYLE", default_value = "absolute")]
+    path_display: PathDisplay,
+
+    /// Report format: text, or json for the whole scan as a single JSON
+    /// document per target, the format --save writes
+    #[arg(long, value_name = "FORMAT", default_value = "text")]
+    format: ReportFormat,
+
+    /// Write the report to FILE instead of stdout, errors still go to stderr
+    #[arg(short = 'o', long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Save the scan to FILE so it can be reported on later with --input,
+    /// takes a single target
+    #[arg(long, value_name = "FILE")]
+    save: Option<PathBuf>,
+
+    /// Report on a scan saved with --save instead of scanning, targets are
+    /// ignored
+    #[arg(short = 'i', long, value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Read default scan options from FILE instead of ./drfs.toml, flags
+    /// given on the command line take precedence
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ReportFormat, String> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown format '{}', expected text or json", s)),
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let matcher = match args.find.as_deref().map(Glob::new).transpose() {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            eprintln!("drfs: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let config = match &args.config {
+        Some(path) => ScanOptions::load(path),
+        None if Path::new(CONFIG_FILE).is_file() => ScanOptions::load(CONFIG_FILE),
+        None => Ok(ScanOptions::default()),
+    };
+    let options = match config.and_then(|config| scan_options(&args, config)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("drfs: {}", e);
+            process::exit(1);
+        }
+    };
+
+    // Opened before scanning so a bad path doesn't waste a whole scan.
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("drfs: {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+
+    if let Some(input) = &args.input {
+        let wrapper = match EntryWrapper::load(input) {
+            Ok(mut wrapper) => {
+                // Only the options affecting reports still matter.
+                wrapper.set_options(options);
+                wrapper
+            }
+            Err(e) => {
+                eprintln!("drfs: {}", e);
+                process::exit(1);
+            }
+        };
+        if let Err(e) =
+            report(&mut out, &wrapper, &args, matcher.as_ref()).and_then(|_| out.flush())
+        {
+            let target = args.output.as_deref().unwrap_or(Path::new("stdout"));
+            eprintln!("drfs: {}: {}", target.display(), e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let (targets, duplicates) = EntryWrapper::dedup_roots(&args.targets);
+    for (duplicate, first) in duplicates {
+        eprintln!(
+            "drfs: {}: same directory as {}, skipping",
+            duplicate.display(),
+            first.display()
+        );
+    }
+    if args.save.is_some() && targets.len() > 1 {
+        eprintln!("drfs: --save takes a single target");
+        process::exit(1);
+    }
+
+    let mut failed = false;
+    for (i, target) in targets.iter().enumerate() {
+        let wrapper = match scan(target, &options, args.progress) {
+            Ok(wrapper) => wrapper,
+            Err(e) => {
+                eprintln!("drfs: {}", e);
+                failed = true;
+                continue;
+            }
+        };
+        if let Some(path) = &args.save {
+            if let Err(e) = wrapper.save(path) {
+                eprintln!("drfs: {}", e);
+                failed = true;
+            }
+        }
+
+        let result = if i > 0 && args.format == ReportFormat::Text {
+            writeln!(out)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result
+            .and_then(|_| report(&mut out, &wrapper, &args, matcher.as_ref()))
+            .and_then(|_| out.flush())
+        {
+            let target = args.output.as_deref().unwrap_or(Path::new("stdout"));
+            eprintln!("drfs: {}: {}", target.display(), e);
+            process::exit(1);
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// The options from `config` with the ones given in `args` replacing them,
+/// excludes from both are combined.
+fn scan_options(args: &Args, config: ScanOptions) -> Result<ScanOptions, GenericError> {
+    let mut exclude = config.exclude;
+    for pattern in &args.exclude {
+        exclude.add(pattern)?;
+    }
+    for path in &args.exclude_from {
+        exclude.extend(ExcludeSet::from_file(path)?);
+    }
+
+    Ok(ScanOptions {
+        threads: args.threads.or(config.threads),
+        collect_times: args
+            .no_times
+            .map_or(config.collect_times, |no_times| !no_times),
+        verify_sizes_every: args.verify_sizes.or(config.verify_sizes_every),
+        max_errors: args.max_errors.or(config.max_errors),
+        low_priority: args.nice.unwrap_or(config.low_priority),
+        device_sizes: args.device_sizes.unwrap_or(config.device_sizes),
+        steal_batch: args.steal_batch.or(config.steal_batch),
+        max_bytes: args.max_bytes.or(config.max_bytes),
+        skip_content: if args.skip_content.is_empty() {
+            config.skip_content
+        } else {
+            args.skip_content.clone()
+        },
+        detect_mounts: args.mounts.unwrap_or(config.detect_mounts),
+        min_depth: args.min_depth.or(config.min_depth),
+        max_depth: args.depth.or(config.max_depth),
+        exclude,
+        time_reads: args.slowest_dirs.is_some() || args.time_reads.unwrap_or(config.time_reads),
+        read_timer: config.read_timer,
+        permission_error_ratio: args
+            .permission_error_ratio
+            .or(config.permission_error_ratio),
+        follow_symlinks: args.follow_symlinks.unwrap_or(config.follow_symlinks),
+        pause: config.pause,
+        progress: config.progress,
+    })
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!("expected a number between 0 and 1, got '{}'", s)),
+    }
+}
+
+/// Scans `target` with `options`, printing scan errors and, with `progress`,
+/// a progress line kept up to date while scanning.
+fn scan(
+    target: &Path,
+    options: &ScanOptions,
+    progress: bool,
+) -> Result<EntryWrapper, GenericError> {
+    let mut wrapper = EntryWrapper::new_with_memstorage(target)?;
+    // Counted from zero for every target.
+    let counter = ScanProgress::default();
+    wrapper.set_options(ScanOptions {
+        progress: counter.clone(),
+        ..options.clone()
+    });
+
+    let errors = if progress {
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            let printer = s.spawn(|| print_progress(&counter, &done));
+            let errors = wrapper.load_entry();
+            done.store(true, Ordering::SeqCst);
+            printer.thread().unpark();
+            errors
+        })
+    } else {
+        wrapper.load_entry()
+    };
+    for e in errors {
+        eprintln!("drfs: {}", e);
+    }
+    if wrapper.errors_truncated() > 0 {
+        eprintln!("drfs: {} more errors not shown", wrapper.errors_truncated());
+    }
+    if wrapper.is_truncated() {
+        eprintln!("drfs: stopped after reaching --max-bytes, results are partial");
+    }
+    Ok(wrapper)
+}
+
+/// Redraws the progress line on stderr until `done` is set, then prints it
+/// once more with the final count.
+fn print_progress(progress: &ScanProgress, done: &AtomicBool) {
+    let started = Instant::now();
+    loop {
+        let finished = done.load(Ordering::SeqCst);
+        // Not a number right at the start, `eta` leaves it unknown then.
+        let rate = progress.done() as f64 / started.elapsed().as_secs_f64();
+        eprint!(
+            "\r{} entries scanned, about {} left, ETA {}  ",
+            progress.done(),
+            progress.remaining(),
+            format_eta(eta(rate, progress.remaining()))
+        );
+        if finished {
+            eprintln!();
+            return;
+        }
+        thread::park_timeout(PROGRESS_INTERVAL);
+    }
+}
+
+fn report(
+    out: &mut dyn Write,
+    wrapper: &EntryWrapper,
+    args: &Args,
+    matcher: Option<&Glob>,
+) -> io::Result<()> {
+    if args.format == ReportFormat::Json {
+        let json = wrapper
+            .to_json()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        return writeln!(out, "{}", json);
+    }
+
+    writeln!(
+        out,
+        "{}: {} entries, {}",
+        wrapper.get_root_path(),
+        wrapper.count_entries(),
+        format_size(wrapper.calculate_size())
+    )?;
+
+    let (children, hardlinks) = if args.dedup_display {
+        let hardlinks: HashSet<String> = wrapper.hardlinked_files().into_iter().collect();
+        (wrapper.child_sizes_dedup(), hardlinks)
+    } else {
+        (wrapper.child_sizes(), HashSet::new())
+    };
+    let largest = children.first().map(|(_, size)| *size).unwrap_or(0);
+    for (path, size) in &children {
+        let ratio = if largest == 0 {
+            0.0
+        } else {
+            *size as f64 / largest as f64
+        };
+        let marker = if hardlinks.contains(path) {
+            "  [hardlink]"
+        } else if wrapper
+            .get_entry(path)
+            .is_some_and(|entry| entry.is_mount_point())
+        {
+            "  [mount]"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "{:>10}  {}  {}{}",
+            format_size(*size),
+            size_bar(ratio, BAR_WIDTH),
+            wrapper.display_path(path, args.path_display),
+            marker
+        )?;
+    }
+
+    if let Some(n) = args.deepest {
+        writeln!(out)?;
+        for (path, depth) in wrapper.deepest_dirs(n) {
+            writeln!(
+                out,
+                "{:>4}  {}",
+                depth,
+                wrapper.display_path(&path, args.path_display)
+            )?;
+        }
+    }
+
+    if let Some(matcher) = matcher {
+        writeln!(out)?;
+        for path in wrapper.find_by(matcher, args.max_results, args.sort) {
+            writeln!(out, "{}", wrapper.display_path(&path, args.path_display))?;
+        }
+    }
+
+    if args.largest_files {
+        writeln!(out)?;
+        print_sizes(out, wrapper, &wrapper.largest_files(args.max_results), args)?;
+    }
+
+    if args.largest_dirs {
+        writeln!(out)?;
+        print_sizes(out, wrapper, &wrapper.largest_dirs(args.max_results), args)?;
+    }
+
+    if let Some(n) = args.slowest_dirs {
+        writeln!(out)?;
+        for (path, time) in wrapper.slowest_dirs(n) {
+            writeln!(
+                out,
+                "{:>10}  {}",
+                format!("{:.1?}", time),
+                wrapper.display_path(&path, args.path_display)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_sizes(
+    out: &mut dyn Write,
+    wrapper: &EntryWrapper,
+    sizes: &[(String, u64)],
+    args: &Args,
+) -> io::Result<()> {
+    for (path, size) in sizes {
+        writeln!(
+            out,
+            "{:>10}  {}",
+            format_size(*size),
+            wrapper.display_path(path, args.path_display)
+        )?;
+    }
+    Ok(())
+}
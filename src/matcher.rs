@@ -0,0 +1,344 @@
+//! Predicates used to select entries in queries such as `EntryWrapper::find`.
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::entry::Entry;
+use crate::GenericError;
+
+/// Decides whether an entry is selected.
+///
+/// Matchers are shared with scan workers, so they have to be `Send + Sync`.
+pub trait Matcher: Send + Sync {
+    fn matches(&self, entry: &Entry) -> bool;
+
+    /// Matches entries selected by both `self` and `other`.
+    fn and<M: Matcher>(self, other: M) -> And<Self, M>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// Matches entries selected by either `self` or `other`.
+    fn or<M: Matcher>(self, other: M) -> Or<Self, M>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn matches(&self, entry: &Entry) -> bool {
+        (**self).matches(entry)
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for &M {
+    fn matches(&self, entry: &Entry) -> bool {
+        (**self).matches(entry)
+    }
+}
+
+/// Glob pattern matched against the entry name, or against the full path if
+/// the pattern contains a path separator.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: glob::Pattern,
+    full_path: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, GenericError> {
+        let full_path = pattern.contains('/') || pattern.contains(std::path::MAIN_SEPARATOR);
+        let pattern = glob::Pattern::new(pattern)
+            .map_err(|e| format!("invalid glob '{}': {}", pattern, e))?;
+        Ok(Glob { pattern, full_path })
+    }
+}
+
+impl Matcher for Glob {
+    fn matches(&self, entry: &Entry) -> bool {
+        if self.full_path {
+            self.pattern
+                .matches_path(Path::new(&entry.get_format_path()))
+        } else {
+            self.pattern.matches(entry.get_name())
+        }
+    }
+}
+
+/// Glob patterns of entries a scan leaves out together with everything below
+/// them, see `ScanOptions::exclude`. Each pattern is matched like a `Glob`,
+/// except that relative patterns with a path separator, like `src/target`,
+/// are matched against the path below the scan root.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct ExcludeSet {
+    patterns: Vec<String>,
+    globs: Vec<Glob>,
+}
+
+impl ExcludeSet {
+    pub fn new<I, S>(patterns: I) -> Result<ExcludeSet, GenericError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut set = ExcludeSet::default();
+        for pattern in patterns {
+            set.add(pattern.as_ref())?;
+        }
+        Ok(set)
+    }
+
+    /// Patterns from `path`, one per line. Blank lines and lines starting
+    /// with `#` are skipped, like in `tar --exclude-from`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ExcludeSet, GenericError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let patterns = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        ExcludeSet::new(patterns).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn add(&mut self, pattern: &str) -> Result<(), GenericError> {
+        self.globs.push(Glob::new(pattern)?);
+        self.patterns.push(pattern.to_string());
+        Ok(())
+    }
+
+    /// Adds every pattern of `other`.
+    pub fn extend(&mut self, other: ExcludeSet) {
+        self.patterns.extend(other.patterns);
+        self.globs.extend(other.globs);
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+impl PartialEq for ExcludeSet {
+    fn eq(&self, other: &ExcludeSet) -> bool {
+        self.patterns == other.patterns
+    }
+}
+
+impl Eq for ExcludeSet {}
+
+impl TryFrom<Vec<String>> for ExcludeSet {
+    type Error = GenericError;
+
+    fn try_from(patterns: Vec<String>) -> Result<ExcludeSet, GenericError> {
+        ExcludeSet::new(patterns)
+    }
+}
+
+impl Matcher for ExcludeSet {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.globs.iter().any(|glob| {
+            if glob.full_path && !Path::new(glob.pattern.as_str()).is_absolute() {
+                glob.pattern.matches_path(&path_below_root(entry))
+            } else {
+                glob.matches(entry)
+            }
+        })
+    }
+}
+
+/// The last `depth` components of the entry path, which is its path relative
+/// to the root of the scan that read it.
+fn path_below_root(entry: &Entry) -> PathBuf {
+    let path = entry.get_format_path();
+    let components: Vec<Component> = Path::new(&path).components().collect();
+    let below = components.len().saturating_sub(entry.get_depth());
+    components[below..].iter().collect()
+}
+
+/// Regular expression searched for in the entry name.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct Regex(regex::Regex);
+
+#[cfg(feature = "regex")]
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, GenericError> {
+        let regex = regex::Regex::new(pattern)
+            .map_err(|e| format!("invalid regex '{}': {}", pattern, e))?;
+        Ok(Regex(regex))
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Matcher for Regex {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.0.is_match(entry.get_name())
+    }
+}
+
+/// Files with one of the given extensions, compared case-insensitively.
+#[derive(Debug, Clone)]
+pub struct Extensions(HashSet<String>);
+
+impl Extensions {
+    pub fn new<I, S>(extensions: I) -> Extensions
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Extensions(
+            extensions
+                .into_iter()
+                .map(|ext| ext.as_ref().trim_start_matches('.').to_lowercase())
+                .collect(),
+        )
+    }
+}
+
+impl Matcher for Extensions {
+    fn matches(&self, entry: &Entry) -> bool {
+        if entry.is_dir() {
+            return false;
+        }
+        Path::new(entry.get_name())
+            .extension()
+            .is_some_and(|ext| self.0.contains(&ext.to_string_lossy().to_lowercase()))
+    }
+}
+
+/// Files of at least this many bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct MinSize(pub u64);
+
+impl Matcher for MinSize {
+    fn matches(&self, entry: &Entry) -> bool {
+        !entry.is_dir() && entry.get_size() >= self.0
+    }
+}
+
+/// Files of at most this many bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct MaxSize(pub u64);
+
+impl Matcher for MaxSize {
+    fn matches(&self, entry: &Entry) -> bool {
+        !entry.is_dir() && entry.get_size() <= self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: Matcher, B: Matcher> Matcher for And<A, B> {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.0.matches(entry) && self.1.matches(entry)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: Matcher, B: Matcher> Matcher for Or<A, B> {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.0.matches(entry) || self.1.matches(entry)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Not<M>(pub M);
+
+impl<M: Matcher> Matcher for Not<M> {
+    fn matches(&self, entry: &Entry) -> bool {
+        !self.0.matches(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_file;
+    use crate::{EntryWrapper, ScanOptions};
+
+    #[test]
+    fn glob_and_extension_matchers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = Entry::new(write_file(tmp.path(), "app.LOG", 1)).unwrap();
+        let txt = Entry::new(write_file(tmp.path(), "notes.txt", 1)).unwrap();
+
+        let glob = Glob::new("app.*").unwrap();
+        assert!(glob.matches(&log));
+        assert!(!glob.matches(&txt));
+
+        let extensions = Extensions::new(vec![".log", "md"]);
+        assert!(extensions.matches(&log));
+        assert!(Not(extensions).matches(&txt));
+        assert!(Glob::new("[").is_err());
+    }
+
+    #[test]
+    fn exclude_set_reads_pattern_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let list = tmp.path().join("excludes");
+        fs::write(&list, "# backups\n*.bak\n\n  target/*  \n").unwrap();
+        let set = ExcludeSet::from_file(&list).unwrap();
+        assert_eq!(set.patterns(), &["*.bak", "target/*"]);
+
+        write_file(tmp.path(), "old.bak", 1);
+        write_file(tmp.path(), "notes.txt", 1);
+        write_file(tmp.path(), "target/out.o", 1);
+        write_file(tmp.path(), "src/target.rs", 1);
+        let mut wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.set_options(ScanOptions {
+            exclude: set,
+            ..ScanOptions::default()
+        });
+        wrapper.load_entry();
+        let root = wrapper.get_root_path();
+        assert_eq!(
+            wrapper.sorted_paths(),
+            vec![
+                root.to_string(),
+                format!("{}/excludes", root),
+                format!("{}/notes.txt", root),
+                format!("{}/src", root),
+                format!("{}/src/target.rs", root),
+                format!("{}/target", root),
+            ]
+        );
+
+        let missing = ExcludeSet::from_file(tmp.path().join("missing")).unwrap_err();
+        assert!(missing.to_string().contains("missing"));
+        fs::write(&list, "[\n").unwrap();
+        assert!(ExcludeSet::from_file(&list).is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_and_min_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "report-1.csv", 100);
+        write_file(tmp.path(), "report-2.csv", 10);
+        write_file(tmp.path(), "summary.csv", 100);
+
+        let wrapper = EntryWrapper::new_with_memstorage(tmp.path()).unwrap();
+        wrapper.load_entry();
+
+        let matcher = Regex::new(r"^report-\d+").unwrap().and(MinSize(50));
+        assert_eq!(
+            wrapper.find(&matcher, None),
+            vec![format!("{}/report-1.csv", wrapper.get_root_path())]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Best-effort lowering of scheduling priority for background scans.
+//!
+//! Only implemented on Linux with the `priority` feature enabled, everywhere
+//! else `lower_thread_priority` reports that it is unsupported and the scan
+//! keeps running at normal priority.
+
+use crate::GenericError;
+
+/// Nice value applied to scan workers.
+pub const LOW_PRIORITY_NICE: i32 = 10;
+
+/// Lowers the cpu priority of the calling thread to `LOW_PRIORITY_NICE` and
+/// moves it to the idle io scheduling class. A thread already running at a
+/// higher nice value keeps it.
+#[cfg(all(feature = "priority", target_os = "linux"))]
+pub fn lower_thread_priority() -> Result<(), GenericError> {
+    use std::io;
+
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+
+    // On Linux all calls with a target of 0 apply to the calling thread only.
+    // -1 is a valid nice value, so errors only show through errno.
+    let nice = unsafe {
+        *libc::__errno_location() = 0;
+        libc::getpriority(libc::PRIO_PROCESS, 0)
+    };
+    if nice == -1 && io::Error::last_os_error().raw_os_error() != Some(0) {
+        return Err(format!("getpriority: {}", io::Error::last_os_error()).into());
+    }
+    // Only ever raising the nice value never needs privileges, lowering it
+    // back from above the target would.
+    if nice < LOW_PRIORITY_NICE
+        && unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, LOW_PRIORITY_NICE) } != 0
+    {
+        return Err(format!("setpriority: {}", io::Error::last_os_error()).into());
+    }
+    let ioprio = IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT;
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) } != 0 {
+        return Err(format!("ioprio_set: {}", io::Error::last_os_error()).into());
+    }
+    Ok(())
+}
+
+#[cfg(not(all(feature = "priority", target_os = "linux")))]
+pub fn lower_thread_priority() -> Result<(), GenericError> {
+    Err("lowering scan priority is not supported on this build".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[cfg(all(feature = "priority", target_os = "linux"))]
+    #[test]
+    fn lower_thread_priority_succeeds_or_is_refused() {
+        // Run on a separate thread so the test harness isn't affected.
+        match thread::spawn(lower_thread_priority).join().unwrap() {
+            Ok(()) => {}
+            // Some sandboxes refuse the io priority change.
+            Err(e) => assert!(e.to_string().contains("ioprio_set"), "{}", e),
+        }
+    }
+
+    #[cfg(all(feature = "priority", target_os = "linux"))]
+    #[test]
+    fn lower_thread_priority_keeps_higher_nice_value() {
+        let nice = thread::spawn(|| {
+            assert_eq!(
+                unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, LOW_PRIORITY_NICE + 5) },
+                0
+            );
+            let _ = lower_thread_priority();
+            unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(nice, LOW_PRIORITY_NICE + 5);
+    }
+
+    #[cfg(not(all(feature = "priority", target_os = "linux")))]
+    #[test]
+    fn lower_thread_priority_is_skipped() {
+        assert!(thread::spawn(lower_thread_priority)
+            .join()
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Mutex, OnceLock};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::Storage;
+
+/// Same as `MemStorage`, but every value is kept serialized and lz4
+/// compressed, trading cpu on each `get` and `set` for memory on large trees.
+///
+/// Values are too small to compress well on their own, so the first value
+/// stored becomes a dictionary every value is compressed against. What
+/// values have in common with it, like field names and path prefixes, then
+/// costs next to nothing.
+///
+/// A value that fails to serialize or deserialize panics the calling thread,
+/// which can't happen for entries.
+#[derive(Debug)]
+pub struct CompressedMemStorage<K, V> {
+    map: Mutex<HashMap<K, Vec<u8>>>,
+    dict: OnceLock<Vec<u8>>,
+    values: PhantomData<fn() -> V>,
+}
+
+impl<K, V> CompressedMemStorage<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new() -> CompressedMemStorage<K, V> {
+        CompressedMemStorage {
+            map: Mutex::new(HashMap::new()),
+            dict: OnceLock::new(),
+            values: PhantomData,
+        }
+    }
+
+    /// Compressed bytes held for all values, keys not included.
+    pub fn stored_bytes(&self) -> usize {
+        self.map.lock().unwrap().values().map(Vec::len).sum()
+    }
+}
+
+impl<K, V> Default for CompressedMemStorage<K, V>
+where
+    K: Eq + Hash,
+{
+    fn default() -> CompressedMemStorage<K, V> {
+        CompressedMemStorage::new()
+    }
+}
+
+impl<K, V> CompressedMemStorage<K, V>
+where
+    V: Serialize + DeserializeOwned,
+{
+    fn compress(&self, value: &V) -> Vec<u8> {
+        let bytes = serde_json::to_vec(value).expect("compressed storage: serialize value");
+        let dict = self.dict.get_or_init(|| bytes.clone());
+        lz4_flex::block::compress_prepend_size_with_dict(&bytes, dict)
+    }
+
+    fn decompress(&self, bytes: &[u8]) -> V {
+        // Anything stored means the dictionary was set.
+        let dict = self.dict.get().map_or(&[][..], Vec::as_slice);
+        let bytes = lz4_flex::block::decompress_size_prepended_with_dict(bytes, dict)
+            .expect("compressed storage: lz4");
+        serde_json::from_slice(&bytes).expect("compressed storage: deserialize value")
+    }
+}
+
+impl<K, V> Storage<K, V> for CompressedMemStorage<K, V>
+where
+    K: Eq + Hash + Clone + Send,
+    V: Serialize + DeserializeOwned,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        let map = self.map.lock().unwrap();
+        map.get(key).map(|bytes| self.decompress(bytes))
+    }
+
+    fn set(&self, key: K, value: V) {
+        let bytes = self.compress(&value);
+        self.map.lock().unwrap().insert(key, bytes);
+    }
+
+    fn remove(&self, key: &K) {
+        self.map.lock().unwrap().remove(key);
+    }
+
+    fn pull_out(&self, key: &K) -> Option<V> {
+        let bytes = self.map.lock().unwrap().remove(key)?;
+        Some(self.decompress(&bytes))
+    }
+
+    fn keys(&self) -> Vec<K> {
+        self.map.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn values(&self) -> Vec<V> {
+        let map = self.map.lock().unwrap();
+        map.values().map(|bytes| self.decompress(bytes)).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.map.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_pull_out() {
+        let storage = CompressedMemStorage::new();
+        storage.set("a".to_string(), vec!["x".repeat(1000)]);
+        storage.set("b".to_string(), vec![]);
+
+        assert_eq!(storage.get(&"a".to_string()), Some(vec!["x".repeat(1000)]));
+        assert!(storage.stored_bytes() < 100);
+        assert_eq!(storage.pull_out(&"b".to_string()), Some(vec![]));
+        assert_eq!(storage.get(&"b".to_string()), None);
+        assert_eq!(storage.len(), 1);
+    }
+}
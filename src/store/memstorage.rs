@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use super::Storage;
+
+#[derive(Debug, Default)]
+pub struct MemStorage<K, V> {
+    map: Mutex<HashMap<K, V>>,
+}
+
+impl<K, V> MemStorage<K, V>
+where
+    K: Eq + Hash,
+{
+    pub fn new() -> MemStorage<K, V> {
+        MemStorage {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, V> Storage<K, V> for MemStorage<K, V>
+where
+    K: Eq + Hash + Clone + Send,
+    V: Clone + Send,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        self.map.lock().unwrap().get(key).cloned()
+    }
+
+    fn set(&self, key: K, value: V) {
+        self.map.lock().unwrap().insert(key, value);
+    }
+
+    fn remove(&self, key: &K) {
+        self.map.lock().unwrap().remove(key);
+    }
+
+    fn pull_out(&self, key: &K) -> Option<V> {
+        self.map.lock().unwrap().remove(key)
+    }
+
+    fn keys(&self) -> Vec<K> {
+        self.map.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn values(&self) -> Vec<V> {
+        self.map.lock().unwrap().values().cloned().collect()
+    }
+
+    fn len(&self) -> usize {
+        self.map.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_pull_out() {
+        let storage = MemStorage::new();
+        storage.set("a".to_string(), 1);
+        storage.set("b".to_string(), 2);
+
+        assert_eq!(storage.get(&"a".to_string()), Some(1));
+        assert_eq!(storage.pull_out(&"b".to_string()), Some(2));
+        assert_eq!(storage.get(&"b".to_string()), None);
+        assert_eq!(storage.len(), 1);
+    }
+}
@@ -0,0 +1,24 @@
+#[cfg(feature = "compressed-storage")]
+pub mod compressedmemstorage;
+pub mod memstorage;
+#[cfg(feature = "sqlite")]
+pub mod sqlitestorage;
+
+/// Key-value store holding scanned entries.
+///
+/// Implementations are shared between worker threads during a scan, so every
+/// method takes `&self` and handles its own synchronization.
+pub trait Storage<K, V>: Send + Sync {
+    fn get(&self, key: &K) -> Option<V>;
+    fn set(&self, key: K, value: V);
+    fn remove(&self, key: &K);
+    /// Removes the value under `key` and returns it.
+    fn pull_out(&self, key: &K) -> Option<V>;
+    fn keys(&self) -> Vec<K>;
+    fn values(&self) -> Vec<V>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
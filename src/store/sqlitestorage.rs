@@ -0,0 +1,168 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::Storage;
+use crate::entry::Entry;
+use crate::GenericError;
+
+/// Storage keeping entries in a SQLite database, one row per path with the
+/// entry serialized as JSON.
+///
+/// All statements go through a single connection behind a mutex, so writes
+/// from scan workers are serialized. The `Storage` trait has no way to report
+/// failures, a database error panics the calling thread.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens the database at `path`, creating it if needed, and keeps any
+    /// entries already stored in it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStorage, GenericError> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        SqliteStorage::with_connection(conn)
+    }
+
+    /// Opens the database at `path` like `open` but removes every stored entry.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<SqliteStorage, GenericError> {
+        let storage = SqliteStorage::open(path)?;
+        storage
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM entries", [])?;
+        Ok(storage)
+    }
+
+    /// A database living in memory only, dropped with the storage.
+    pub fn in_memory() -> Result<SqliteStorage, GenericError> {
+        SqliteStorage::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<SqliteStorage, GenericError> {
+        // Scans write one row per entry, syncing every one of them is far too slow.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS entries (path TEXT PRIMARY KEY, entry TEXT NOT NULL)",
+            [],
+        )?;
+        Ok(SqliteStorage {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn column<T: rusqlite::types::FromSql>(&self, sql: &str) -> Vec<T> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare_cached(sql).expect("sqlite storage: prepare");
+        statement
+            .query_map([], |row| row.get(0))
+            .and_then(|rows| rows.collect())
+            .expect("sqlite storage: select")
+    }
+}
+
+fn encode(entry: &Entry) -> String {
+    serde_json::to_string(entry).expect("sqlite storage: serialize entry")
+}
+
+fn decode(text: &str) -> Entry {
+    serde_json::from_str(text).expect("sqlite storage: deserialize entry")
+}
+
+impl Storage<String, Entry> for SqliteStorage {
+    fn get(&self, key: &String) -> Option<Entry> {
+        let conn = self.conn.lock().unwrap();
+        let text: Option<String> = conn
+            .prepare_cached("SELECT entry FROM entries WHERE path = ?1")
+            .and_then(|mut statement| {
+                statement
+                    .query_row(params![key], |row| row.get(0))
+                    .optional()
+            })
+            .expect("sqlite storage: select");
+        text.map(|text| decode(&text))
+    }
+
+    fn set(&self, key: String, value: Entry) {
+        let entry = encode(&value);
+        let conn = self.conn.lock().unwrap();
+        conn.prepare_cached(
+            "INSERT INTO entries (path, entry) VALUES (?1, ?2)
+             ON CONFLICT(path) DO UPDATE SET entry = excluded.entry",
+        )
+        .and_then(|mut statement| statement.execute(params![key, entry]))
+        .expect("sqlite storage: upsert");
+    }
+
+    fn remove(&self, key: &String) {
+        let conn = self.conn.lock().unwrap();
+        conn.prepare_cached("DELETE FROM entries WHERE path = ?1")
+            .and_then(|mut statement| statement.execute(params![key]))
+            .expect("sqlite storage: delete");
+    }
+
+    fn pull_out(&self, key: &String) -> Option<Entry> {
+        let conn = self.conn.lock().unwrap();
+        let text: Option<String> = conn
+            .prepare_cached("DELETE FROM entries WHERE path = ?1 RETURNING entry")
+            .and_then(|mut statement| {
+                statement
+                    .query_row(params![key], |row| row.get(0))
+                    .optional()
+            })
+            .expect("sqlite storage: delete");
+        text.map(|text| decode(&text))
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.column("SELECT path FROM entries")
+    }
+
+    fn values(&self) -> Vec<Entry> {
+        self.column::<String>("SELECT entry FROM entries")
+            .iter()
+            .map(|text| decode(text))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM entries", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .expect("sqlite storage: count") as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_get_pull_out_survive_reopening() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a"), b"1234").unwrap();
+        let db = tmp.path().join("scan.db");
+        let entry = Entry::new(tmp.path().join("a")).unwrap();
+        let key = entry.get_format_path();
+
+        let storage = SqliteStorage::create(&db).unwrap();
+        storage.set(key.clone(), entry.clone());
+        storage.set(key.clone(), entry);
+        assert_eq!(storage.len(), 1);
+        drop(storage);
+
+        let storage = SqliteStorage::open(&db).unwrap();
+        assert_eq!(storage.keys(), vec![key.clone()]);
+        assert_eq!(storage.get(&key).unwrap().get_size(), 4);
+        assert_eq!(storage.pull_out(&key).unwrap().get_size(), 4);
+        assert!(storage.get(&key).is_none());
+        assert!(storage.pull_out(&key).is_none());
+        assert!(storage.is_empty());
+    }
+}
@@ -0,0 +1,18 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Creates `rel` below `root` filled with `size` bytes, creating parent directories.
+pub fn write_file(root: &Path, rel: &str, size: usize) -> PathBuf {
+    let path = root.join(rel);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    fs::write(&path, vec![b'x'; size]).unwrap();
+    path
+}
+
+pub fn make_dir(root: &Path, rel: &str) -> PathBuf {
+    let path = root.join(rel);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
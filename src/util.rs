@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// Formats a byte count using binary units, e.g. `1536` -> `1.5 KiB`.
+pub fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Renders `ratio` (clamped to `0.0..=1.0`) as a bar of `width` cells,
+/// e.g. `0.5` at width 8 -> `████░░░░`.
+pub fn size_bar(ratio: f64, width: usize) -> String {
+    let filled = filled_cells(ratio, width);
+    let mut bar = "█".repeat(filled);
+    bar.push_str(&"░".repeat(width - filled));
+    bar
+}
+
+fn filled_cells(ratio: f64, width: usize) -> usize {
+    if ratio.is_nan() {
+        return 0;
+    }
+    (ratio.clamp(0.0, 1.0) * width as f64).round() as usize
+}
+
+/// How timestamps are rendered. Absolute formats use UTC.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimeFormat {
+    /// e.g. `2020-01-31T12:00:00Z`
+    #[default]
+    Rfc3339,
+    /// Age relative to now, e.g. `3 days ago`.
+    Relative,
+    /// A `strftime` style format string, e.g. `%Y-%m-%d %H:%M`.
+    Custom(String),
+}
+
+/// Formats `time` according to `format`, relative times are measured from now.
+pub fn format_time(time: SystemTime, format: &TimeFormat) -> String {
+    format_time_at(time, SystemTime::now(), format)
+}
+
+/// Same as `format_time`, with relative times measured from `now`.
+pub fn format_time_at(time: SystemTime, now: SystemTime, format: &TimeFormat) -> String {
+    let datetime: DateTime<Utc> = time.into();
+    match format {
+        TimeFormat::Rfc3339 => datetime.to_rfc3339_opts(SecondsFormat::Secs, true),
+        TimeFormat::Relative => format_relative(time, now),
+        TimeFormat::Custom(fmt) => {
+            let items: Vec<Item> = StrftimeItems::new(fmt).collect();
+            let mut formatted = String::new();
+            if items.contains(&Item::Error)
+                || write!(
+                    formatted,
+                    "{}",
+                    datetime.format_with_items(items.into_iter())
+                )
+                .is_err()
+            {
+                return format!("invalid time format '{}'", fmt);
+            }
+            formatted
+        }
+    }
+}
+
+fn format_relative(time: SystemTime, now: SystemTime) -> String {
+    let secs = match now.duration_since(time) {
+        Ok(age) => age.as_secs(),
+        Err(_) => return "in the future".to_string(),
+    };
+
+    let (amount, unit) = if secs < 60 {
+        (secs, "second")
+    } else if secs < 60 * 60 {
+        (secs / 60, "minute")
+    } else if secs < 24 * 60 * 60 {
+        (secs / (60 * 60), "hour")
+    } else {
+        (secs / (24 * 60 * 60), "day")
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    format!("{} {}{} ago", amount, unit, plural)
+}
+
+/// Time left to process `remaining` entries at `rate` entries per second,
+/// `None` while the rate is still unknown, e.g. right at the start.
+pub fn eta(rate: f64, remaining: u64) -> Option<Duration> {
+    if remaining == 0 {
+        return Some(Duration::ZERO);
+    }
+    if !rate.is_finite() || rate <= 0.0 {
+        return None;
+    }
+    let secs = remaining as f64 / rate;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Formats an `eta` result for a progress line, e.g. `1:05:09` or `3:07`,
+/// and `--:--` when it is unknown.
+pub fn format_eta(eta: Option<Duration>) -> String {
+    let secs = match eta {
+        Some(eta) => eta.as_secs() + u64::from(eta.subsec_nanos() > 0),
+        None => return "--:--".to_string(),
+    };
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+/// How paths and names are ordered in listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Plain byte order, `file10` before `file2`.
+    #[default]
+    Lexicographic,
+    /// Runs of digits compare by value, `file2` before `file10`.
+    Natural,
+    /// Like `Natural`, ignoring case outside of digit runs.
+    NaturalCaseInsensitive,
+}
+
+impl SortOrder {
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortOrder::Lexicographic => a.cmp(b),
+            SortOrder::Natural => natural_cmp(a, b, false),
+            SortOrder::NaturalCaseInsensitive => {
+                natural_cmp(a, b, true).then_with(|| natural_cmp(a, b, false))
+            }
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortOrder, String> {
+        match s {
+            "lexicographic" => Ok(SortOrder::Lexicographic),
+            "natural" => Ok(SortOrder::Natural),
+            "natural-ci" => Ok(SortOrder::NaturalCaseInsensitive),
+            _ => Err(format!(
+                "unknown sort order '{}', expected lexicographic, natural or natural-ci",
+                s
+            )),
+        }
+    }
+}
+
+/// Compares digit runs by numeric value and everything else char by char.
+/// Equal values with different leading zeros fall back to the shorter run first.
+fn natural_cmp(a: &str, b: &str, ignore_case: bool) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (ca, cb) = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) => (ca, cb),
+        };
+
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let (da, rest_a) = split_digits(a);
+            let (db, rest_b) = split_digits(b);
+            let (va, vb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+            let ordering = va
+                .len()
+                .cmp(&vb.len())
+                .then_with(|| va.cmp(vb))
+                .then_with(|| da.len().cmp(&db.len()));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            a = rest_a;
+            b = rest_b;
+            continue;
+        }
+
+        let ordering = if ignore_case {
+            ca.to_lowercase().cmp(cb.to_lowercase())
+        } else {
+            ca.cmp(&cb)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        a = &a[ca.len_utf8()..];
+        b = &b[cb.len_utf8()..];
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_size_units() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn size_bar_fills_proportionally() {
+        assert_eq!(filled_cells(0.0, 10), 0);
+        assert_eq!(filled_cells(0.26, 10), 3);
+        assert_eq!(filled_cells(1.0, 10), 10);
+        assert_eq!(filled_cells(2.0, 10), 10);
+        assert_eq!(filled_cells(f64::NAN, 10), 0);
+        assert_eq!(size_bar(0.5, 8), "████░░░░");
+    }
+
+    #[test]
+    fn format_time_absolute() {
+        use std::time::Duration;
+
+        // 2020-01-31T12:00:00Z
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_580_472_000);
+        assert_eq!(
+            format_time(time, &TimeFormat::Rfc3339),
+            "2020-01-31T12:00:00Z"
+        );
+        assert_eq!(
+            format_time(time, &TimeFormat::Custom("%Y-%m-%d %H:%M".to_string())),
+            "2020-01-31 12:00"
+        );
+        assert_eq!(
+            format_time(time, &TimeFormat::Custom("%Q".to_string())),
+            "invalid time format '%Q'"
+        );
+    }
+
+    #[test]
+    fn format_time_relative_buckets() {
+        use std::time::Duration;
+
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_580_472_000);
+        let ago =
+            |secs| format_time_at(now - Duration::from_secs(secs), now, &TimeFormat::Relative);
+        assert_eq!(ago(0), "0 seconds ago");
+        assert_eq!(ago(1), "1 second ago");
+        assert_eq!(ago(59), "59 seconds ago");
+        assert_eq!(ago(60), "1 minute ago");
+        assert_eq!(ago(3 * 60 * 60 + 59), "3 hours ago");
+        assert_eq!(ago(3 * 24 * 60 * 60), "3 days ago");
+        assert_eq!(
+            format_time_at(now + Duration::from_secs(5), now, &TimeFormat::Relative),
+            "in the future"
+        );
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        let mut names = vec!["file10", "file2", "File3", "file1", "file02"];
+        names.sort_by(|a, b| SortOrder::Lexicographic.compare(a, b));
+        assert_eq!(names, vec!["File3", "file02", "file1", "file10", "file2"]);
+
+        names.sort_by(|a, b| SortOrder::Natural.compare(a, b));
+        assert_eq!(names, vec!["File3", "file1", "file2", "file02", "file10"]);
+
+        names.sort_by(|a, b| SortOrder::NaturalCaseInsensitive.compare(a, b));
+        assert_eq!(names, vec!["file1", "file2", "file02", "File3", "file10"]);
+        assert_eq!(
+            SortOrder::Natural.compare("file2", "file10"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn eta_from_rate_and_remaining() {
+        assert_eq!(eta(100.0, 1500), Some(Duration::from_secs(15)));
+        assert_eq!(eta(0.5, 3), Some(Duration::from_secs(6)));
+        assert_eq!(eta(0.0, 10), None);
+        assert_eq!(eta(f64::NAN, 10), None);
+        assert_eq!(eta(f64::INFINITY, 10), None);
+        assert_eq!(eta(0.0, 0), Some(Duration::ZERO));
+
+        assert_eq!(format_eta(eta(100.0, 1500)), "0:15");
+        assert_eq!(format_eta(Some(Duration::from_millis(187_200))), "3:08");
+        assert_eq!(format_eta(Some(Duration::from_secs(3909))), "1:05:09");
+        assert_eq!(format_eta(eta(0.0, 10)), "--:--");
+    }
+}
@@ -0,0 +1,592 @@
+//! The parallel work-stealing engine behind scans, usable on its own
+//! through `walk_parallel` for arbitrary per-path work.
+
+use std::any::Any;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{self, Display};
+use std::fs::{self, Metadata};
+use std::iter;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicIsize, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::deque::{Injector, Stealer, Worker};
+
+use crate::entry::{inode, ScanOptions};
+use crate::priority;
+use crate::GenericError;
+
+/// Pauses a scan from another thread, see `ScanOptions::pause`.
+///
+/// Clones share the same flag. While it is set every worker parks once it
+/// is done with its current task, queued tasks stay queued and the scan only
+/// ends after a `resume`.
+#[derive(Debug, Clone, Default)]
+pub struct PauseFlag {
+    paused: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl PauseFlag {
+    pub fn pause(&self) {
+        *self.paused.0.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.paused.0.lock().unwrap() = false;
+        self.paused.1.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.0.lock().unwrap()
+    }
+
+    fn wait_while_paused(&self) {
+        let (paused, resumed) = &*self.paused;
+        let _guard = resumed
+            .wait_while(paused.lock().unwrap(), |paused| *paused)
+            .unwrap();
+    }
+}
+
+/// Flags compare by state only, so options differing in nothing else are equal.
+impl PartialEq for PauseFlag {
+    fn eq(&self, other: &PauseFlag) -> bool {
+        self.is_paused() == other.is_paused()
+    }
+}
+
+/// Decides the read time recorded for each directory when scanning with
+/// `ScanOptions::time_reads`, see `ScanOptions::read_timer`.
+///
+/// The default records the measured time. `new` gets the directory and the
+/// measured time and returns the time to record instead, e.g. to make read
+/// times predictable in tests.
+#[derive(Clone, Default)]
+pub struct ReadTimer {
+    time: Option<Arc<ReadTimeFn>>,
+}
+
+type ReadTimeFn = dyn Fn(&Path, Duration) -> Duration + Send + Sync;
+
+impl ReadTimer {
+    pub fn new<F>(time: F) -> ReadTimer
+    where
+        F: Fn(&Path, Duration) -> Duration + Send + Sync + 'static,
+    {
+        ReadTimer {
+            time: Some(Arc::new(time)),
+        }
+    }
+
+    pub(crate) fn read_time(&self, dir: &Path, measured: Duration) -> Duration {
+        match &self.time {
+            Some(time) => time(dir, measured),
+            None => measured,
+        }
+    }
+}
+
+impl fmt::Debug for ReadTimer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.time.is_some() {
+            "custom"
+        } else {
+            "measured"
+        };
+        f.debug_tuple("ReadTimer").field(&kind).finish()
+    }
+}
+
+/// Timers are no setting, so any two compare equal.
+impl PartialEq for ReadTimer {
+    fn eq(&self, _other: &ReadTimer) -> bool {
+        true
+    }
+}
+
+/// Counts the work of a running scan for another thread to report progress
+/// on, see `ScanOptions::progress`.
+///
+/// Clones share the same counters. `remaining` only knows about entries that
+/// were already found, so it grows while new directories are read and is an
+/// estimate until the scan ends.
+#[derive(Debug, Clone, Default)]
+pub struct ScanProgress {
+    counts: Arc<(AtomicU64, AtomicU64)>,
+}
+
+impl ScanProgress {
+    /// Entries processed so far.
+    pub fn done(&self) -> u64 {
+        self.counts.0.load(Ordering::Relaxed)
+    }
+
+    /// Entries found but not processed yet.
+    pub fn remaining(&self) -> u64 {
+        self.counts.1.load(Ordering::Relaxed)
+    }
+
+    fn queued(&self, tasks: usize) {
+        self.counts.1.fetch_add(tasks as u64, Ordering::Relaxed);
+    }
+
+    fn finished(&self) {
+        self.counts.1.fetch_sub(1, Ordering::Relaxed);
+        self.counts.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Progress is no setting, so any two compare equal.
+impl PartialEq for ScanProgress {
+    fn eq(&self, _other: &ScanProgress) -> bool {
+        true
+    }
+}
+
+/// Walks everything below `root` in parallel and calls `visitor` once for
+/// every path with its metadata, `root` included, without building any
+/// entries or storage. Like a regular scan, symlinks below `root` are
+/// reported as links unless `options.follow_symlinks` is set, and a
+/// directory reached again through another path isn't walked twice. Only
+/// the worker settings and `follow_symlinks` of `options` apply.
+///
+/// `visitor` is called concurrently from the workers. Returns the paths
+/// that couldn't be read.
+pub fn walk_parallel<P, F>(root: P, options: &ScanOptions, visitor: F) -> Vec<GenericError>
+where
+    P: AsRef<Path>,
+    F: Fn(&Path, &Metadata) + Sync,
+{
+    let root = root.as_ref();
+    let errors = Mutex::new(vec![]);
+    let error = |p: &Path, e: &dyn Display| {
+        errors
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}", p.display(), e).into())
+    };
+    // Directories already read, so a followed symlink leading back up the
+    // tree isn't walked round and round.
+    let visited = Mutex::new(HashSet::new());
+
+    let panics = run_parallel(options, vec![root.to_path_buf()], |p: PathBuf, push| {
+        let metadata = if p == root || options.follow_symlinks {
+            fs::metadata(&p)
+        } else {
+            fs::symlink_metadata(&p)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(e) => return error(&p, &e),
+        };
+        visitor(&p, &metadata);
+        if !metadata.is_dir() {
+            return;
+        }
+        let first_visit =
+            inode(&metadata).is_none_or(|inode| visited.lock().unwrap().insert(inode));
+        if !first_visit {
+            return error(
+                &p,
+                &"directory was already walked through another path, skipping possible \
+                      symlink cycle",
+            );
+        }
+        match fs::read_dir(&p) {
+            Ok(read_dir) => {
+                for child in read_dir {
+                    match child {
+                        Ok(child) => push(child.path()),
+                        Err(e) => error(&p, &e),
+                    }
+                }
+            }
+            Err(e) => error(&p, &e),
+        }
+    });
+
+    let mut errors = errors.into_inner().unwrap();
+    errors.extend(panics);
+    errors
+}
+
+/// Processes `initial` tasks and every task pushed by `process` on a pool of
+/// `options.thread_count()` workers, returning once the queue has fully drained.
+/// A single worker runs on the calling thread unless it has to lower its
+/// priority, which would stick to the caller.
+///
+/// A panic inside `process` only abandons the task that caused it, the rest of
+/// the queue is still processed and the panic is returned as an error.
+pub(crate) fn run_parallel<T, F>(
+    options: &ScanOptions,
+    initial: Vec<T>,
+    process: F,
+) -> Vec<GenericError>
+where
+    T: Send,
+    F: Fn(T, &mut dyn FnMut(T)) + Sync,
+{
+    run_parallel_with_state(options, initial, || (), |task, _, push| process(task, push)).1
+}
+
+/// Same as `run_parallel`, but every worker also owns a `S` made by `init`
+/// that `process` can update without synchronization. Returns each worker's
+/// final state, so totals are combined once at the end instead of every
+/// worker contending on a shared atomic per task.
+pub(crate) fn run_parallel_with_state<T, S, I, F>(
+    options: &ScanOptions,
+    initial: Vec<T>,
+    init: I,
+    process: F,
+) -> (Vec<S>, Vec<GenericError>)
+where
+    T: Send,
+    S: Send,
+    I: Fn() -> S + Sync,
+    F: Fn(T, &mut S, &mut dyn FnMut(T)) + Sync,
+{
+    options.progress.queued(initial.len());
+    if options.thread_count() == 1 && !options.low_priority {
+        return run_on_current_thread(initial, options, init, process);
+    }
+
+    let injector = Injector::new();
+    // Tasks queued or in progress, workers stop once it drops to zero.
+    let pending = AtomicIsize::new(initial.len() as isize);
+    for task in initial {
+        injector.push(task);
+    }
+
+    let workers: Vec<Worker<T>> = (0..options.thread_count())
+        .map(|_| Worker::new_fifo())
+        .collect();
+    let stealers: Vec<Stealer<T>> = workers.iter().map(|w| w.stealer()).collect();
+    let panics = Mutex::new(vec![]);
+
+    let result = crossbeam::scope(|s| {
+        let handles: Vec<_> = workers
+            .into_iter()
+            .map(|worker| {
+                let injector = &injector;
+                let stealers = &stealers;
+                let pending = &pending;
+                let init = &init;
+                let process = &process;
+                let panics = &panics;
+                let low_priority = options.low_priority;
+                let steal_batch = options.steal_batch_limit();
+                s.spawn(move |_| {
+                    if low_priority {
+                        // Best effort, the scan runs at normal priority otherwise.
+                        let _ = priority::lower_thread_priority();
+                    }
+                    let queues = Queues {
+                        local: &worker,
+                        global: injector,
+                        stealers,
+                        steal_batch,
+                    };
+                    let mut state = init();
+                    worker_loop(&queues, pending, options, &mut state, process, panics);
+                    state
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| match handle.join() {
+                Ok(state) => Some(state),
+                Err(payload) => {
+                    panics.lock().unwrap().push(panic_error(payload));
+                    None
+                }
+            })
+            .collect()
+    });
+
+    let mut panics = panics.into_inner().unwrap();
+    let states = match result {
+        Ok(states) => states,
+        Err(payload) => {
+            panics.push(panic_error(payload));
+            vec![]
+        }
+    };
+    (states, panics)
+}
+
+/// `run_parallel_with_state` without any pool, for when it would only add
+/// overhead.
+fn run_on_current_thread<T, S, I, F>(
+    initial: Vec<T>,
+    options: &ScanOptions,
+    init: I,
+    process: F,
+) -> (Vec<S>, Vec<GenericError>)
+where
+    I: Fn() -> S,
+    F: Fn(T, &mut S, &mut dyn FnMut(T)),
+{
+    let mut queue: VecDeque<T> = initial.into();
+    let mut state = init();
+    let mut panics = vec![];
+    while let Some(task) = queue.pop_front() {
+        options.pause.wait_while_paused();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            process(task, &mut state, &mut |t| {
+                options.progress.queued(1);
+                queue.push_back(t)
+            })
+        }));
+        if let Err(payload) = result {
+            panics.push(panic_error(payload));
+        }
+        options.progress.finished();
+    }
+    (vec![state], panics)
+}
+
+/// The queues a worker takes tasks from.
+struct Queues<'a, T> {
+    local: &'a Worker<T>,
+    global: &'a Injector<T>,
+    stealers: &'a [Stealer<T>],
+    steal_batch: Option<usize>,
+}
+
+fn worker_loop<T, S, F>(
+    queues: &Queues<T>,
+    pending: &AtomicIsize,
+    options: &ScanOptions,
+    state: &mut S,
+    process: &F,
+    panics: &Mutex<Vec<GenericError>>,
+) where
+    F: Fn(T, &mut S, &mut dyn FnMut(T)),
+{
+    loop {
+        options.pause.wait_while_paused();
+        match find_task(queues) {
+            Some(task) => {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    process(task, state, &mut |t| {
+                        pending.fetch_add(1, Ordering::SeqCst);
+                        options.progress.queued(1);
+                        queues.local.push(t);
+                    })
+                }));
+                if let Err(payload) = result {
+                    panics.lock().unwrap().push(panic_error(payload));
+                }
+                options.progress.finished();
+                pending.fetch_sub(1, Ordering::SeqCst);
+            }
+            None => {
+                if pending.load(Ordering::SeqCst) == 0 {
+                    break;
+                }
+                thread::yield_now();
+            }
+        }
+    }
+}
+
+fn panic_error(payload: Box<dyn Any + Send>) -> GenericError {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    };
+    format!("scan worker panicked: {}", message).into()
+}
+
+fn find_task<T>(queues: &Queues<T>) -> Option<T> {
+    let local = queues.local;
+    local.pop().or_else(|| {
+        iter::repeat_with(|| match queues.steal_batch {
+            None => queues
+                .global
+                .steal_batch_and_pop(local)
+                .or_else(|| queues.stealers.iter().map(|s| s.steal()).collect()),
+            Some(limit) => queues
+                .global
+                .steal_batch_with_limit_and_pop(local, limit)
+                .or_else(|| {
+                    queues
+                        .stealers
+                        .iter()
+                        .map(|s| s.steal_batch_with_limit_and_pop(local, limit))
+                        .collect()
+                }),
+        })
+        .find(|s| !s.is_retry())
+        .and_then(|s| s.success())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_file;
+    use std::collections::HashMap;
+
+    #[test]
+    fn walk_parallel_visits_every_path_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..10 {
+            for j in 0..10 {
+                write_file(tmp.path(), &format!("d{}/e{}/f{}", i, j % 4, j), 1);
+            }
+        }
+
+        let visits: Mutex<HashMap<PathBuf, usize>> = Mutex::new(HashMap::new());
+        let options = ScanOptions {
+            threads: Some(4),
+            ..ScanOptions::default()
+        };
+        let errors = walk_parallel(tmp.path(), &options, |p, _| {
+            *visits.lock().unwrap().entry(p.to_path_buf()).or_insert(0) += 1;
+        });
+        assert!(errors.is_empty());
+
+        let visits = visits.into_inner().unwrap();
+        assert_eq!(visits.len(), 1 + 10 + 10 * 4 + 10 * 10);
+        assert!(visits.values().all(|count| *count == 1));
+        assert!(visits.contains_key(tmp.path()));
+
+        let missing = walk_parallel(tmp.path().join("missing"), &options, |_, _| {
+            panic!("nothing to visit")
+        });
+        assert_eq!(missing.len(), 1);
+    }
+
+    #[test]
+    fn progress_counts_every_processed_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            write_file(tmp.path(), &format!("d{}/f{}", i % 4, i), 1);
+        }
+
+        for threads in [1, 4] {
+            let options = ScanOptions {
+                threads: Some(threads),
+                ..ScanOptions::default()
+            };
+            let progress = options.progress.clone();
+            walk_parallel(tmp.path(), &options, |_, _| {});
+            assert_eq!(progress.done(), 1 + 4 + 20);
+            assert_eq!(progress.remaining(), 0);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_parallel_does_not_loop_through_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_file(tmp.path(), "a/f", 1);
+        std::os::unix::fs::symlink("..", tmp.path().join("a/up")).unwrap();
+
+        let walk = |follow_symlinks| {
+            let options = ScanOptions {
+                follow_symlinks,
+                ..ScanOptions::default()
+            };
+            let files = Mutex::new(vec![]);
+            let errors = walk_parallel(tmp.path(), &options, |p, metadata| {
+                if !metadata.is_dir() {
+                    files.lock().unwrap().push(p.to_path_buf());
+                }
+            });
+            let mut files = files.into_inner().unwrap();
+            files.sort();
+            (files, errors.len())
+        };
+
+        assert_eq!(
+            walk(false),
+            (vec![tmp.path().join("a/f"), tmp.path().join("a/up")], 0)
+        );
+        assert_eq!(walk(true), (vec![tmp.path().join("a/f")], 1));
+    }
+
+    #[test]
+    fn single_thread_runs_on_calling_thread() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            write_file(tmp.path(), &format!("d{}/e{}/f", i % 5, i), 1);
+        }
+
+        let walk = |threads| {
+            let options = ScanOptions {
+                threads: Some(threads),
+                ..ScanOptions::default()
+            };
+            let caller = thread::current().id();
+            let paths = Mutex::new(vec![]);
+            let on_caller = Mutex::new(true);
+            let errors = walk_parallel(tmp.path(), &options, |p, _| {
+                paths.lock().unwrap().push(p.to_path_buf());
+                *on_caller.lock().unwrap() &= thread::current().id() == caller;
+            });
+            assert!(errors.is_empty());
+            let mut paths = paths.into_inner().unwrap();
+            paths.sort();
+            (paths, on_caller.into_inner().unwrap())
+        };
+
+        let (single, single_on_caller) = walk(1);
+        let (pooled, pooled_on_caller) = walk(4);
+        assert!(single_on_caller);
+        assert!(!pooled_on_caller);
+        assert_eq!(single, pooled);
+        assert_eq!(single.len(), 1 + 5 + 20 + 20);
+    }
+
+    #[test]
+    fn paused_walk_makes_no_progress_until_resumed() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..50 {
+            write_file(tmp.path(), &format!("d{}/e{}/f", i % 5, i), 1);
+        }
+
+        for threads in [1, 4] {
+            let options = ScanOptions {
+                threads: Some(threads),
+                ..ScanOptions::default()
+            };
+            let pause = options.pause.clone();
+            let visits = Arc::new(AtomicIsize::new(0));
+            let walk = {
+                let root = tmp.path().to_path_buf();
+                let visits = visits.clone();
+                thread::spawn(move || {
+                    walk_parallel(root, &options, |_, _| {
+                        if visits.fetch_add(1, Ordering::SeqCst) == 10 {
+                            options.pause.pause();
+                        }
+                    })
+                })
+            };
+
+            // Give the workers time to finish the tasks they were on.
+            while !pause.is_paused() {
+                thread::yield_now();
+            }
+            thread::sleep(std::time::Duration::from_millis(100));
+            let paused_at = visits.load(Ordering::SeqCst);
+            thread::sleep(std::time::Duration::from_millis(100));
+            assert_eq!(visits.load(Ordering::SeqCst), paused_at);
+            assert!(paused_at < 1 + 5 + 50 + 50);
+            assert!(!walk.is_finished());
+
+            pause.resume();
+            assert!(walk.join().unwrap().is_empty());
+            assert_eq!(visits.load(Ordering::SeqCst), 1 + 5 + 50 + 50);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::fs;
+use std::process::Command;
+
+fn drfs() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_drfs"))
+}
+
+#[test]
+fn output_writes_report_to_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("tree");
+    fs::create_dir_all(target.join("sub")).unwrap();
+    fs::write(target.join("a.txt"), vec![0; 10]).unwrap();
+    fs::write(target.join("sub/b.txt"), vec![0; 20]).unwrap();
+    let report = tmp.path().join("report.txt");
+
+    let output = drfs()
+        .arg(&target)
+        .arg("--largest-files")
+        .arg("--output")
+        .arg(&report)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let root = fs::canonicalize(&target).unwrap();
+    let written = fs::read_to_string(&report).unwrap();
+    let mut lines = written.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        format!("{}: 3 entries, 30 B", root.display())
+    );
+    assert!(written.contains(&format!("20 B  {}", root.join("sub/b.txt").display())));
+
+    let json = tmp.path().join("report.json");
+    let output = drfs()
+        .arg(&target)
+        .arg("--format")
+        .arg("json")
+        .arg("--output")
+        .arg(&json)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let written: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(written["root"], root.display().to_string());
+}
+
+#[test]
+fn saved_scan_is_reported_from_input() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("tree");
+    fs::create_dir_all(target.join("sub")).unwrap();
+    fs::write(target.join("a.txt"), vec![0; 10]).unwrap();
+    fs::write(target.join("sub/b.txt"), vec![0; 20]).unwrap();
+    let saved = tmp.path().join("scan.json");
+
+    let scanned = drfs()
+        .arg(&target)
+        .arg("--largest-files")
+        .arg("--save")
+        .arg(&saved)
+        .output()
+        .unwrap();
+    assert!(scanned.status.success());
+    fs::remove_dir_all(&target).unwrap();
+
+    let loaded = drfs()
+        .arg("--largest-files")
+        .arg("-i")
+        .arg(&saved)
+        .output()
+        .unwrap();
+    assert!(loaded.status.success());
+    assert_eq!(loaded.stdout, scanned.stdout);
+}
+
+#[test]
+fn unwritable_output_fails_before_scanning() {
+    let tmp = tempfile::tempdir().unwrap();
+
+    let output = drfs()
+        .arg(tmp.path())
+        .arg("--output")
+        .arg(tmp.path().join("missing/report.txt"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("drfs: "));
+    assert!(stderr.contains("missing/report.txt"));
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_duplicate_target_is_scanned_once() {
+    let tmp = tempfile::tempdir().unwrap();
+    let real = tmp.path().join("real");
+    fs::create_dir(&real).unwrap();
+    fs::write(real.join("a.txt"), vec![0; 10]).unwrap();
+    let link = tmp.path().join("link");
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+
+    let output = drfs().arg(&real).arg(&link).output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let summaries: Vec<&str> = stdout
+        .lines()
+        .filter(|l| l.contains(" entries, "))
+        .collect();
+    assert_eq!(summaries.len(), 1);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("{}: same directory as", link.display())));
+}
+
+#[test]
+fn config_file_sets_defaults_and_flags_override_it() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("tree");
+    fs::create_dir_all(target.join("sub")).unwrap();
+    fs::write(target.join("top.txt"), vec![0; 10]).unwrap();
+    fs::write(target.join("sub/nested.txt"), vec![0; 20]).unwrap();
+    fs::write(tmp.path().join("drfs.toml"), "min_depth = 2\n").unwrap();
+    let root = fs::canonicalize(&target).unwrap();
+    let top = root.join("top.txt").display().to_string();
+    let nested = root.join("sub/nested.txt").display().to_string();
+
+    // Picked up from the current directory.
+    let output = drfs()
+        .current_dir(tmp.path())
+        .arg(&target)
+        .arg("--find")
+        .arg("*.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line == nested));
+    assert!(!stdout.lines().any(|line| line == top));
+
+    let output = drfs()
+        .arg(&target)
+        .arg("--config")
+        .arg(tmp.path().join("drfs.toml"))
+        .arg("--min-depth")
+        .arg("1")
+        .arg("--find")
+        .arg("*.txt")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line == top));
+
+    #[cfg(unix)]
+    {
+        fs::create_dir(tmp.path().join("outside")).unwrap();
+        fs::write(tmp.path().join("outside/big.bin"), vec![0; 1000]).unwrap();
+        std::os::unix::fs::symlink("../outside", target.join("link")).unwrap();
+        fs::write(tmp.path().join("links.toml"), "follow_symlinks = true\n").unwrap();
+        let size = |extra: &[&str]| {
+            let output = drfs()
+                .arg(&target)
+                .arg("--config")
+                .arg(tmp.path().join("links.toml"))
+                .args(extra)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            stdout.lines().next().unwrap().to_string()
+        };
+        assert!(size(&[]).ends_with(", 1.0 KiB"));
+        assert!(!size(&["--follow-symlinks=false"]).contains("KiB"));
+    }
+
+    fs::write(tmp.path().join("bad.toml"), "min_dept = 2\n").unwrap();
+    let output = drfs()
+        .arg(&target)
+        .arg("--config")
+        .arg(tmp.path().join("bad.toml"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("bad.toml"));
+}
+
+#[test]
+fn exclude_skips_matching_directories() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("tree");
+    fs::create_dir_all(target.join("node_modules/dep")).unwrap();
+    fs::create_dir_all(target.join("src/target")).unwrap();
+    fs::write(target.join("node_modules/dep/index.js"), vec![0; 100]).unwrap();
+    fs::write(target.join("src/target/out.o"), vec![0; 100]).unwrap();
+    fs::write(target.join("src/main.rs"), vec![0; 10]).unwrap();
+
+    let output = drfs()
+        .arg(&target)
+        .arg("-x")
+        .arg("node_modules")
+        .arg("--exclude")
+        .arg("target")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let root = fs::canonicalize(&target).unwrap();
+    assert!(stdout.starts_with(&format!("{}: 2 entries, 10 B", root.display())));
+
+    let output = drfs().arg(&target).arg("-x").arg("[").output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn exclude_from_skips_listed_patterns() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("tree");
+    fs::create_dir_all(target.join("sub")).unwrap();
+    fs::write(target.join("a.txt"), vec![0; 10]).unwrap();
+    fs::write(target.join("a.txt.bak"), vec![0; 100]).unwrap();
+    fs::write(target.join("sub/b.bak"), vec![0; 100]).unwrap();
+    let excludes = tmp.path().join("excludes");
+    fs::write(&excludes, "*.bak\n").unwrap();
+
+    let output = drfs()
+        .arg(&target)
+        .arg("--exclude-from")
+        .arg(&excludes)
+        .arg("--find")
+        .arg("*")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let root = fs::canonicalize(&target).unwrap();
+    assert!(stdout.starts_with(&format!("{}: 2 entries, 10 B", root.display())));
+    assert!(!stdout.contains(".bak"));
+
+    let output = drfs()
+        .arg(&target)
+        .arg("--exclude-from")
+        .arg(tmp.path().join("missing"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("drfs: "));
+    assert!(stderr.contains("missing"));
+}
+
+#[test]
+fn max_results_applies_after_sort() {
+    let tmp = tempfile::tempdir().unwrap();
+    for name in ["file1", "file2", "file10"] {
+        fs::write(tmp.path().join(name), vec![0; 1]).unwrap();
+    }
+    let root = fs::canonicalize(tmp.path()).unwrap();
+
+    let output = drfs()
+        .arg(tmp.path())
+        .arg("--find")
+        .arg("file*")
+        .arg("--sort")
+        .arg("natural")
+        .arg("--max-results")
+        .arg("2")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let found: Vec<&str> = stdout
+        .lines()
+        .skip_while(|l| !l.is_empty())
+        .skip(1)
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            root.join("file1").display().to_string(),
+            root.join("file2").display().to_string(),
+        ]
+    );
+}
+
+#[test]
+fn exclude_matches_path_patterns_below_the_target() {
+    let tmp = tempfile::tempdir().unwrap();
+    let target = tmp.path().join("tree");
+    fs::create_dir_all(target.join("src/target")).unwrap();
+    fs::write(target.join("src/target/out.o"), vec![0; 100]).unwrap();
+    fs::write(target.join("src/main.rs"), vec![0; 10]).unwrap();
+    let root = fs::canonicalize(&target).unwrap();
+
+    let output = drfs()
+        .arg(&target)
+        .arg("-x")
+        .arg("src/target")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!("{}: 2 entries, 10 B", root.display())));
+
+    let output = drfs().arg(&target).arg("-x").arg("src/*").output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!("{}: 1 entries, 0 B", root.display())));
+}
+
+#[test]
+fn progress_line_reports_final_count() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("sub/a.txt"), vec![0; 10]).unwrap();
+
+    let output = drfs().arg(tmp.path()).arg("--progress").output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let last = stderr.lines().last().unwrap().rsplit('\r').next().unwrap();
+    assert_eq!(last.trim_end(), "2 entries scanned, about 0 left, ETA 0:00");
+}